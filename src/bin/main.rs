@@ -1,273 +1,378 @@
-use std::{f64::consts::PI, fs::File, io::Write};
-
-use rt_new::features::{
-    rotation_x, rotation_y, rotation_z, scaling, translation, view_transformation, Camera, Color,
-    Light, Material, Matrice, Object, Pattern, Point, Shape, Vector, World, WHITE,
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant, SystemTime},
 };
-use serde::{Deserialize, Serialize};
 
-fn main() {
-    let file_path = "config.yaml";
-    let file = File::open(file_path).expect("Unable to open file");
-    let res: Config = serde_yaml::from_reader(file).unwrap();
-    println!("{:?}", res);
-    res.ray_tracer();
+use clap::{value_parser, Parser, Subcommand};
+use rt_new::scene::{self, AnimationConfig, CameraConfig, Config, ConfigError, Format};
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Renders YAML, JSON or TOML scene descriptions",
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Scene files to render, one after another, or - for standard input
+    #[arg(default_value = "config.yaml")]
+    scenes: Vec<PathBuf>,
+    /// Format of a scene read from standard input (yaml, json or toml)
+    #[arg(long, value_parser = parse_format, default_value = "yaml")]
+    input_format: Format,
+    /// Output image (.ppm or .png), or frame directory for animations [default: samples/<output_file>.<format>]
+    #[arg(short, long, value_parser = parse_output)]
+    output: Option<PathBuf>,
+    /// Image format when the output path does not name one (ppm or png)
+    #[arg(short, long, value_parser = parse_image_format)]
+    format: Option<ImageFormat>,
+    /// Image width in pixels
+    #[arg(long, value_parser = value_parser!(u32).range(1..=i32::MAX as i64))]
+    width: Option<u32>,
+    /// Image height in pixels
+    #[arg(long, value_parser = value_parser!(u32).range(1..=i32::MAX as i64))]
+    height: Option<u32>,
+    /// Samples per pixel
+    #[arg(long)]
+    samples: Option<usize>,
+    /// Worker threads [default: one per core]
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+    /// Hide the progress bar
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print the parsed scene and a timing summary
+    #[arg(short, long)]
+    verbose: bool,
+    /// Only check that the scenes load
+    #[arg(long)]
+    dry_run: bool,
+    /// Render again whenever the scene or a file it includes changes, as a
+    /// quick preview first
+    #[arg(short, long, conflicts_with = "dry_run")]
+    watch: bool,
 }
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
-struct Config {
-    light: Option<LightConfig>,
-    camera: Option<CameraConfig>,
-    objects: Option<Vec<ObjectConfig>>,
-    output_file: Option<String>,
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the JSON Schema of the scene format
+    Schema {
+        /// Write the schema to a file instead
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check scene files against the schema
+    Validate {
+        /// Scene files, or - for standard input
+        #[arg(required = true)]
+        scenes: Vec<PathBuf>,
+        /// Format of a scene read from standard input (yaml, json or toml)
+        #[arg(long, value_parser = parse_format, default_value = "yaml")]
+        input_format: Format,
+    },
 }
 
-impl Config {
-    pub fn ray_tracer(self) {
-        let light = self.light.map_or(Light::default(), Light::from);
-        let camera = self.camera.map_or(Camera::default(), Camera::from);
-        let objects: Option<Vec<Object>> = self
-            .objects
-            .map(|objs| objs.iter().map(|o| Object::from(o.clone())).collect());
-        let world = objects.map_or(World::new(light.clone()), |objs| {
-            let mut w = World::new(light);
-            w.add_shapes(objs);
-            w
-        });
-        File::create(
-            String::from("samples/") + &self.output_file.unwrap_or("example1".to_string()) + ".ppm",
-        )
-        .unwrap()
-        .write_all(camera.render(&world).to_ppm().as_bytes())
-        .unwrap();
+fn main() {
+    let cli = Cli::parse();
+    match &cli.command {
+        Some(Command::Schema { output }) => return print_schema(output.as_deref()),
+        Some(Command::Validate {
+            scenes,
+            input_format,
+        }) => return validate(scenes, *input_format),
+        None => {}
+    }
+    if cli.output.is_some() && cli.scenes.len() > 1 {
+        fail("--output can only be used with a single scene");
+    }
+    if cli.watch && (cli.scenes.len() > 1 || is_stdin(&cli.scenes[0])) {
+        fail("--watch needs a single scene file");
+    }
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
+    if cli.watch {
+        watch(&cli.scenes[0], &cli);
+    }
+    for scene in &cli.scenes {
+        let mut config = cli
+            .load(scene)
+            .unwrap_or_else(|e| fail(&format!("{}: {}", scene.display(), e)));
+        cli.override_camera(&mut config);
+        if cli.verbose {
+            eprintln!("{:#?}", config);
+        }
+        if cli.dry_run {
+            config
+                .check()
+                .unwrap_or_else(|e| fail(&format!("{}: {}", scene.display(), e)));
+            println!("{}: ok", scene.display());
+            continue;
+        }
+        let start = Instant::now();
+        render(config, &cli).unwrap_or_else(|e| fail(&format!("{}: {}", scene.display(), e)));
+        if cli.verbose {
+            eprintln!(
+                "{}: rendered in {:.2}s",
+                scene.display(),
+                start.elapsed().as_secs_f64()
+            );
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
-struct LightConfig {
-    position: Option<TupleConfig>,
-    color: Option<TupleConfig>,
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1)
 }
 
-impl From<LightConfig> for Light {
-    fn from(value: LightConfig) -> Self {
-        Light::new(
-            value.position.map_or(Point::default(), Point::from),
-            value.color.map_or(WHITE, Color::from),
-        )
+fn print_schema(output: Option<&Path>) {
+    let schema = serde_json::to_string_pretty(&scene::schema()).unwrap() + "\n";
+    match output {
+        Some(path) => fs::write(path, schema)
+            .unwrap_or_else(|e| fail(&format!("Unable to write {}: {}", path.display(), e))),
+        None => print!("{}", schema),
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
-struct TupleConfig(f64, f64, f64);
+fn parse_format(name: &str) -> Result<Format, String> {
+    Format::from_name(name).ok_or_else(|| format!("unknown format `{}`", name))
+}
 
-impl From<TupleConfig> for Point {
-    fn from(value: TupleConfig) -> Self {
-        Point::new(value.0, value.1, value.2)
-    }
+// the image formats the renderer writes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ImageFormat {
+    Ppm,
+    Png,
 }
 
-impl From<TupleConfig> for Vector {
-    fn from(value: TupleConfig) -> Self {
-        Vector::new(value.0, value.1, value.2)
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
     }
 }
 
-impl From<TupleConfig> for Color {
-    fn from(value: TupleConfig) -> Self {
-        Color::new(value.0, value.1, value.2)
+fn parse_image_format(name: &str) -> Result<ImageFormat, String> {
+    match name {
+        "ppm" => Ok(ImageFormat::Ppm),
+        "png" => Ok(ImageFormat::Png),
+        _ => Err(format!(
+            "unsupported image format `{}`, expected ppm or png",
+            name
+        )),
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
-struct CameraConfig {
-    from: Option<TupleConfig>,
-    to: Option<TupleConfig>,
-    up: Option<TupleConfig>,
-    width: Option<i32>,
-    height: Option<i32>,
-    fov_radian: Option<f64>,
+// an output path naming a file must name one of the supported formats
+fn parse_output(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    if let Some(extension) = path.extension() {
+        parse_image_format(&extension.to_string_lossy())?;
+    }
+    Ok(path)
 }
 
-impl From<CameraConfig> for Camera {
-    fn from(value: CameraConfig) -> Self {
-        let mut res = Camera::new(
-            value.width.unwrap_or(1200) as f64,
-            value.height.unwrap_or(1200) as f64,
-            value.fov_radian.unwrap_or(PI / 3.5),
-        );
-        res.transform = view_transformation(
-            value.from.map_or(Point::new(3.0, 8.5, -14.5), Point::from),
-            value.to.map_or(Point::new(0.0, 0.0, 0.0), Point::from),
-            value.up.map_or(Vector::new(0.0, 1.0, 0.0), Vector::from),
-        );
-        res
-    }
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
-struct ObjectConfig {
-    shape: Option<ShapeConfig>,
-    material: Option<MaterialConfig>,
-    transformation: Option<Vec<TransformationConfig>>,
+// the text of a scene and the format it is written in
+fn read(path: &Path, stdin_format: Format) -> Result<(String, Format), ConfigError> {
+    let (text, format) = match is_stdin(path) {
+        true => (io::read_to_string(io::stdin()), stdin_format),
+        false => (fs::read_to_string(path), Format::from_path(path)),
+    };
+    let text = text.map_err(|e| ConfigError::new("", &e.to_string()))?;
+    Ok((text, format))
 }
 
-impl ObjectConfig {
-    pub fn as_object(&self) -> Object {
-        Object::default()
+// previews render at a quarter of the width and height
+const PREVIEW_DIVISOR: i32 = 4;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// renders the scene each time one of its files changes: a preview first, then
+// the full image unless the files changed again while the preview rendered
+fn watch(scene: &Path, cli: &Cli) -> ! {
+    let mut files = vec![scene.to_path_buf()];
+    loop {
+        let (loaded, stamps) = load_stamped(scene, &mut files);
+        let rendered = loaded.and_then(|mut config| {
+            cli.override_camera(&mut config);
+            render(config.preview(PREVIEW_DIVISOR), cli)?;
+            match modified(&files) == stamps {
+                true => render(config, cli),
+                false => Ok(()),
+            }
+        });
+        if let Err(e) = rendered {
+            eprintln!("error: {}: {}", scene.display(), e);
+        }
+        if !cli.quiet {
+            eprintln!("{}: waiting for changes", scene.display());
+        }
+        while modified(&files) == stamps {
+            thread::sleep(POLL_INTERVAL);
+        }
     }
 }
 
-impl From<ObjectConfig> for Object {
-    fn from(value: ObjectConfig) -> Self {
-        Object::new(
-            value.material.map_or(Material::default(), Material::from),
-            value.shape.map_or(Shape::Sphere, Shape::from),
-            value.transformation.map_or(Matrice::default(), |list| {
-                list.iter()
-                    .fold(Matrice::default(), |acc, x| acc * Matrice::from(x.clone()))
-            }),
-        )
+// loads the scene along with when its files last changed, stamped before
+// reading them so that a save made during the load still counts as a change;
+// loads again when the scene names other files than `files` or one of them
+// changed meanwhile
+fn load_stamped(
+    scene: &Path,
+    files: &mut Vec<PathBuf>,
+) -> (Result<Config, ConfigError>, Vec<Option<SystemTime>>) {
+    loop {
+        let stamps = modified(files);
+        let mut read = vec![];
+        let loaded = Config::load_recording(scene, &mut read);
+        if read == *files && modified(files) == stamps {
+            return (loaded, stamps);
+        }
+        *files = read;
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
-struct MaterialConfig {
-    color: Option<TupleConfig>,
-    ambient: Option<f64>,
-    diffuse: Option<f64>,
-    specular: Option<f64>,
-    shininess: Option<f64>,
-    reflective: Option<f64>,
-    transparency: Option<f64>,
-    refractive_index: Option<f64>,
-    pattern: Option<PatternConfig>,
+// when each file last changed, if it can be read at all
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
 }
 
-impl From<MaterialConfig> for Material {
-    fn from(value: MaterialConfig) -> Self {
-        let mut builder = Material::builder();
-        if let Some(ambient) = value.ambient {
-            builder = builder.ambient(ambient);
-        }
-        if let Some(diffuse) = value.diffuse {
-            builder = builder.diffuse(diffuse);
-        }
-        if let Some(specular) = value.specular {
-            builder = builder.specular(specular);
-        }
-        if let Some(shininess) = value.shininess {
-            builder = builder.shininess(shininess);
-        }
-        if let Some(color) = value.color {
-            builder = builder.color(Color::from(color));
+fn validate(scenes: &[PathBuf], stdin_format: Format) {
+    let mut valid = true;
+    for path in scenes {
+        let document = read(path, stdin_format).and_then(|(text, format)| format.parse(&text));
+        let errors = match document {
+            Ok(document) => scene::validate(&document).err().unwrap_or_default(),
+            Err(e) => vec![e],
+        };
+        for error in &errors {
+            eprintln!("{}: {}", path.display(), error);
         }
-        if let Some(pattern) = value.pattern {
-            builder = builder.pattern(Pattern::from(pattern))
+        if errors.is_empty() {
+            println!("{}: ok", path.display());
         }
-        if let Some(reflective) = value.reflective {
-            builder = builder.reflective(reflective);
-        }
-        if let Some(transparency) = value.transparency {
-            builder = builder.transparency(transparency);
-        }
-        if let Some(refractive_index) = value.refractive_index {
-            builder = builder.refractive_index(refractive_index);
-        }
-        builder.build()
+        valid &= errors.is_empty();
+    }
+    if !valid {
+        process::exit(1);
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct PatternConfig {
-    pattern_type: Option<String>,
-    color_a: Option<TupleConfig>,
-    color_b: Option<TupleConfig>,
-}
-//     Checker(TupleConfig, TupleConfig),
-//     Stripe(TupleConfig, TupleConfig),
-//     Gradient(TupleConfig, TupleConfig),
-//     Ring(TupleConfig, TupleConfig),
-// }
+impl Cli {
+    fn load(&self, scene: &Path) -> Result<Config, ConfigError> {
+        match is_stdin(scene) {
+            // includes are relative to the working directory
+            true => {
+                let (text, format) = read(scene, self.input_format)?;
+                Config::load_text(&text, format, Path::new(""))
+            }
+            false => Config::load(scene),
+        }
+    }
+
+    fn override_camera(&self, config: &mut Config) {
+        let camera = config.camera.get_or_insert_with(CameraConfig::default);
+        // clap keeps both within the range of an i32
+        camera.width = self.width.map(|w| w as i32).or(camera.width);
+        camera.height = self.height.map(|h| h as i32).or(camera.height);
+        camera.samples = self.samples.or(camera.samples);
+    }
 
-impl From<PatternConfig> for Pattern {
-    fn from(value: PatternConfig) -> Self {
-        match value.pattern_type.unwrap().as_str() {
-            "checker" => Pattern::checker(
-                Color::from(value.color_a.unwrap()),
-                Color::from(value.color_b.unwrap()),
-            ),
-            "stripe" => Pattern::stripe(
-                Color::from(value.color_a.unwrap()),
-                Color::from(value.color_b.unwrap()),
-            ),
-            "gradient" => Pattern::gradient(
-                Color::from(value.color_a.unwrap()),
-                Color::from(value.color_b.unwrap()),
-            ),
-            "ring" => Pattern::ring(
-                Color::from(value.color_a.unwrap()),
-                Color::from(value.color_b.unwrap()),
-            ),
-            _ => Pattern::default(),
+    fn image_path(&self, output_file: Option<&str>) -> PathBuf {
+        let format = self.format.unwrap_or(ImageFormat::Ppm).extension();
+        match &self.output {
+            Some(path) if path.extension().is_some() => path.clone(),
+            Some(path) => path.with_extension(format),
+            None => Path::new("samples")
+                .join(output_file.unwrap_or("example1"))
+                .with_extension(format),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
-struct ShapeConfig {
-    shape_type: Option<String>,
-    min: Option<f64>,
-    max: Option<f64>,
-    closed: Option<bool>,
+fn render(mut config: Config, cli: &Cli) -> Result<(), ConfigError> {
+    if let Some(animation) = config.animation.take() {
+        return render_animation(&animation, &config, cli);
+    }
+    let path = cli.image_path(config.output_file.as_deref());
+    render_image(config, &path, cli)
 }
 
-impl From<ShapeConfig> for Shape {
-    fn from(value: ShapeConfig) -> Self {
-        match value.shape_type.unwrap().as_str() {
-            "sphere" => Shape::Sphere,
-            "cone" => Shape::Cone(
-                value.min.unwrap(),
-                value.max.unwrap(),
-                value.closed.unwrap(),
-            ),
-            "cube" => Shape::Cube,
-            "cylinder" => Shape::Cylinder(
-                value.min.unwrap(),
-                value.max.unwrap(),
-                value.closed.unwrap(),
-            ),
-            "plane" => Shape::Plane,
-            _ => Shape::default(),
+// renders one image of the scene to `path` along with its passes, denoised
+// and with statistics if the scene asks for them
+fn render_image(config: Config, path: &Path, cli: &Cli) -> Result<(), ConfigError> {
+    let mut options = config.tile_options()?;
+    options.progress = !cli.quiet;
+    let report = config.render.as_ref().and_then(|r| r.stats.clone());
+    let aovs = config.aovs()?;
+    let denoiser = config.denoiser()?;
+    // auxiliary passes are only collected when something needs them
+    options.aovs = denoiser.is_some() || !aovs.is_empty();
+    let (world, camera) = config.build()?;
+    let (mut image, buffers, stats) = camera
+        .render_with_aovs(&world, &options)
+        .map_err(|e| unwritable(options.checkpoint.as_deref().unwrap_or(path), e))?;
+    match report.as_deref() {
+        Some("print") => print!("{}", stats),
+        Some("json") => println!("{}", serde_json::to_string_pretty(&stats).unwrap()),
+        _ if cli.verbose => eprint!("{}", stats),
+        _ => {}
+    }
+    if let (Some(denoiser), Some(buffers)) = (&denoiser, &buffers) {
+        image = denoiser.apply(&image, buffers);
+    }
+    image.save(path).map_err(|e| unwritable(path, e))?;
+    if let Some(buffers) = buffers {
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        for aov in aovs {
+            let aov_path = path.with_file_name(format!("{}_{}.png", stem, aov.name()));
+            buffers
+                .to_canvas(aov)
+                .save(&aov_path)
+                .map_err(|e| unwritable(&aov_path, e))?;
         }
     }
+    Ok(())
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct TransformationConfig {
-    transformation_type: Option<String>,
-    vec: Option<TupleConfig>,
-    rad: Option<f64>,
+fn render_animation(
+    animation: &AnimationConfig,
+    base: &Config,
+    cli: &Cli,
+) -> Result<(), ConfigError> {
+    let output_dir = cli
+        .output
+        .clone()
+        .or(animation.output_dir.clone().map(PathBuf::from))
+        .unwrap_or(PathBuf::from("frames"));
+    let format = cli.format.unwrap_or(ImageFormat::Png).extension();
+    fs::create_dir_all(&output_dir).map_err(|e| unwritable(&output_dir, e))?;
+    for frame in animation.frames() {
+        // each frame is its own scene, so a checkpoint only resumes that frame
+        let scene = animation.frame(base, frame)?;
+        let path = output_dir.join(format!("frame_{:04}.{}", frame, format));
+        render_image(scene, &path, cli)?;
+    }
+    Ok(())
 }
 
-impl From<TransformationConfig> for Matrice {
-    fn from(value: TransformationConfig) -> Self {
-        match value.transformation_type.unwrap().as_str() {
-            "translation" => {
-                let tuple = value.vec.unwrap();
-                translation(tuple.0, tuple.1, tuple.2)
-            }
-            "scaling" => {
-                let tuple = value.vec.unwrap();
-                scaling(tuple.0, tuple.1, tuple.2)
-            }
-            "rotation_x" => rotation_x(value.rad.unwrap()),
-            "rotation_y" => rotation_y(value.rad.unwrap()),
-            "rotation_z" => rotation_z(value.rad.unwrap()),
-            _ => Matrice::default(),
-        }
-    }
+// a file that could not be written is reported like a scene error, so that
+// `--watch` keeps running
+fn unwritable(path: &Path, e: impl fmt::Display) -> ConfigError {
+    ConfigError::new("", &format!("unable to write `{}`: {}", path.display(), e))
 }
-//TODO:
-//object transform
-//file name
-//defaults
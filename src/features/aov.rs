@@ -0,0 +1,203 @@
+use super::{canvas::Canvas, color::Color, consts::BLACK, vector::Vector};
+
+// what a primary ray saw at its first visible surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovSample {
+    pub depth: f64,
+    pub normal: Vector,
+    pub albedo: Color,
+    pub object: usize,
+    pub shadowed: bool,
+}
+
+impl AovSample {
+    // one value for a pixel from the `hits` among its `rays`: none when most
+    // rays missed, otherwise the mean of the hits, the object hit most often
+    // and whether most hits were in shadow
+    pub fn combine(hits: &[AovSample], rays: usize) -> Option<AovSample> {
+        if hits.is_empty() || hits.len() * 2 < rays {
+            return None;
+        }
+        let share = 1.0 / hits.len() as f64;
+        let mut normal = Vector::new(0.0, 0.0, 0.0);
+        let mut albedo = BLACK;
+        for hit in hits {
+            normal = normal + hit.normal;
+            albedo = albedo + hit.albedo * share;
+        }
+        let count = |object: usize| hits.iter().filter(|h| h.object == object).count();
+        let object = hits.iter().map(|h| h.object).max_by_key(|&o| count(o))?;
+        Some(AovSample {
+            depth: hits.iter().map(|h| h.depth).sum::<f64>() * share,
+            normal: match normal.magnitude() > 0.0 {
+                true => normal.normalize(),
+                false => hits[0].normal,
+            },
+            albedo,
+            object,
+            shadowed: hits.iter().filter(|h| h.shadowed).count() * 2 > hits.len(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aov {
+    Depth,
+    Normal,
+    Albedo,
+    ObjectId,
+    Shadow,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [
+        Aov::Depth,
+        Aov::Normal,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::Shadow,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object_id",
+            Aov::Shadow => "shadow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.into_iter().find(|aov| aov.name() == name)
+    }
+}
+
+// auxiliary buffers in row-major order; pixels whose ray missed hold None
+#[derive(Debug, Clone, PartialEq)]
+pub struct AovBuffers {
+    pub width: usize,
+    pub height: usize,
+    pub samples: Vec<Option<AovSample>>,
+}
+
+impl AovBuffers {
+    pub fn at(&self, x: usize, y: usize) -> Option<&AovSample> {
+        self.samples[y * self.width + x].as_ref()
+    }
+
+    // maps a pass to displayable colors: depth is normalised to the farthest
+    // hit, normals go from [-1, 1] to [0, 1] and objects get distinct hues
+    pub fn to_canvas(&self, aov: Aov) -> Canvas {
+        let max_depth = self
+            .samples
+            .iter()
+            .flatten()
+            .map(|s| s.depth)
+            .fold(0.0, f64::max);
+        let mut canvas = Canvas::new(self.width, self.height);
+        for (i, sample) in self.samples.iter().enumerate() {
+            let Some(sample) = sample else { continue };
+            canvas.canvas[i / self.width][i % self.width] = match aov {
+                Aov::Depth => {
+                    let d = sample.depth / max_depth;
+                    Color::new(d, d, d)
+                }
+                Aov::Normal => {
+                    let n = sample.normal;
+                    Color::new(n.x() + 1.0, n.y() + 1.0, n.z() + 1.0) * 0.5
+                }
+                Aov::Albedo => sample.albedo,
+                Aov::ObjectId => object_color(sample.object),
+                Aov::Shadow if sample.shadowed => BLACK,
+                Aov::Shadow => Color::new(1.0, 1.0, 1.0),
+            };
+        }
+        canvas
+    }
+}
+
+// spreads consecutive indices around the hue circle by the golden ratio
+fn object_color(index: usize) -> Color {
+    let hue = ((index + 1) as f64 * 0.618_033_988_75).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    match hue as usize {
+        0 => Color::new(1.0, x, 0.0),
+        1 => Color::new(x, 1.0, 0.0),
+        2 => Color::new(0.0, 1.0, x),
+        3 => Color::new(0.0, x, 1.0),
+        4 => Color::new(x, 0.0, 1.0),
+        _ => Color::new(1.0, 0.0, x),
+    }
+}
+
+#[cfg(test)]
+mod aov_tests {
+    use crate::features::RED;
+
+    use super::*;
+
+    fn buffers() -> AovBuffers {
+        let sample = |depth, object, shadowed| AovSample {
+            depth,
+            normal: Vector::new(0.0, 0.0, -1.0),
+            albedo: RED,
+            object,
+            shadowed,
+        };
+        AovBuffers {
+            width: 3,
+            height: 1,
+            samples: vec![
+                Some(sample(2.0, 0, false)),
+                Some(sample(4.0, 1, true)),
+                None,
+            ],
+        }
+    }
+
+    #[test]
+    fn samples_of_a_pixel_are_combined() {
+        let [near, far, _] = buffers().samples.try_into().unwrap();
+        let (near, far) = (near.unwrap(), far.unwrap());
+        let pixel = AovSample::combine(&[near, far, near], 4).unwrap();
+        assert_eq!(pixel.depth, 8.0 / 3.0);
+        assert_eq!(pixel.normal, near.normal);
+        assert_eq!(pixel.albedo, RED);
+        assert_eq!(pixel.object, 0);
+        assert!(!pixel.shadowed);
+        assert_eq!(AovSample::combine(&[far], 3), None);
+    }
+
+    #[test]
+    fn aov_names_round_trip() {
+        for aov in Aov::ALL {
+            assert_eq!(Aov::from_name(aov.name()), Some(aov));
+        }
+        assert_eq!(Aov::from_name("beauty"), None);
+    }
+
+    #[test]
+    fn depth_is_normalised_to_farthest_hit() {
+        let canvas = buffers().to_canvas(Aov::Depth);
+        assert_eq!(canvas.canvas[0][0], Color::new(0.5, 0.5, 0.5));
+        assert_eq!(canvas.canvas[0][1], Color::new(1.0, 1.0, 1.0));
+        assert_eq!(canvas.canvas[0][2], BLACK);
+    }
+
+    #[test]
+    fn normals_are_remapped() {
+        let canvas = buffers().to_canvas(Aov::Normal);
+        assert_eq!(canvas.canvas[0][0], Color::new(0.5, 0.5, 0.0));
+    }
+
+    #[test]
+    fn shadow_mask_and_object_ids() {
+        let b = buffers();
+        let shadow = b.to_canvas(Aov::Shadow);
+        assert_eq!(shadow.canvas[0][0], Color::new(1.0, 1.0, 1.0));
+        assert_eq!(shadow.canvas[0][1], BLACK);
+        let ids = b.to_canvas(Aov::ObjectId);
+        assert_ne!(ids.canvas[0][0], ids.canvas[0][1]);
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    f64::consts::PI,
+    path::{Path, PathBuf},
+};
+
+use image::ImageResult;
+
+use super::{color::Color, consts::BLACK, vector::Vector};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Background {
+    Color(Color),
+    // colors straight down and straight up, blended by the ray's elevation
+    Gradient(Color, Color),
+    Sky(Sky),
+    Environment(EnvironmentMap),
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(BLACK)
+    }
+}
+
+impl Background {
+    pub fn at(&self, direction: &Vector) -> Color {
+        match self {
+            Background::Color(color) => *color,
+            Background::Gradient(bottom, top) => {
+                let fraction = (direction.normalize().y() + 1.0) / 2.0;
+                *bottom + (*top - *bottom) * fraction
+            }
+            Background::Sky(sky) => sky.at(direction),
+            Background::Environment(map) => map.at(direction),
+        }
+    }
+}
+
+// Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight".
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Sky {
+    pub sun_direction: Vector,
+    pub turbidity: f64,
+}
+
+// brings the zenith luminance (kcd/m2) of a clear noon sky to roughly 1.0
+const SKY_LUMINANCE_SCALE: f64 = 1.0 / 30.0;
+
+impl Sky {
+    pub fn new(sun_direction: Vector, turbidity: f64) -> Self {
+        Sky {
+            sun_direction: sun_direction.normalize(),
+            turbidity,
+        }
+    }
+
+    pub fn at(&self, direction: &Vector) -> Color {
+        let direction = direction.normalize();
+        let t = self.turbidity;
+        // keep the sun and view directions above the horizon, where the model holds
+        let theta_s = self
+            .sun_direction
+            .y()
+            .clamp(0.0, 1.0)
+            .acos()
+            .min(PI / 2.0 - 0.01);
+        let theta = direction.y().clamp(0.001, 1.0).acos();
+        let gamma = direction
+            .dot_product(&self.sun_direction)
+            .clamp(-1.0, 1.0)
+            .acos();
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = zenith_chromaticity(
+            t,
+            theta_s,
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        );
+        let zenith_yc = zenith_chromaticity(
+            t,
+            theta_s,
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        );
+
+        let coefficients_y = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let coefficients_x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let coefficients_yc = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+        let relative = |c: &[f64; 5]| perez(c, theta, gamma) / perez(c, 0.0, theta_s);
+
+        let luminance = zenith_y * relative(&coefficients_y) * SKY_LUMINANCE_SCALE;
+        let x = zenith_x * relative(&coefficients_x);
+        let y = zenith_yc * relative(&coefficients_yc);
+        xyy_to_rgb(x, y, luminance)
+    }
+}
+
+fn zenith_chromaticity(t: f64, theta_s: f64, t2: [f64; 4], t1: [f64; 4], t0: [f64; 4]) -> f64 {
+    let cubic =
+        |c: [f64; 4]| c[0] * theta_s.powi(3) + c[1] * theta_s.powi(2) + c[2] * theta_s + c[3];
+    t * t * cubic(t2) + t * cubic(t1) + cubic(t0)
+}
+
+fn perez(c: &[f64; 5], theta: f64, gamma: f64) -> f64 {
+    (1.0 + c[0] * (c[1] / theta.cos()).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos().powi(2))
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    let big_x = x * luminance / y;
+    let big_z = (1.0 - x - y) * luminance / y;
+    Color::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+}
+
+// Latitude-longitude image wrapped around the scene: the centre column looks
+// down -z and the top row looks straight up.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnvironmentMap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+    // the file the map was loaded from, if any
+    pub path: Option<PathBuf>,
+}
+
+impl EnvironmentMap {
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let image = image::open(&path)?.into_rgb32f();
+        let pixels = image
+            .pixels()
+            .map(|p| Color::new(p[0] as f64, p[1] as f64, p[2] as f64))
+            .collect();
+        Ok(EnvironmentMap {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels,
+            path: Some(path.as_ref().to_path_buf()),
+        })
+    }
+
+    pub fn at(&self, direction: &Vector) -> Color {
+        let direction = direction.normalize();
+        let u = 0.5 + direction.x().atan2(-direction.z()) / (2.0 * PI);
+        let v = direction.y().clamp(-1.0, 1.0).acos() / PI;
+        // bilinear lookup, wrapping around horizontally
+        let fx = u * self.width as f64 - 0.5;
+        let fy = (v * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+        let (x0, y0) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - x0, fy - y0);
+        let column = |x: f64| (x as i64).rem_euclid(self.width as i64) as usize;
+        let row = |y: f64| (y as usize).min(self.height - 1);
+        let pixel = |x: f64, y: f64| self.pixels[row(y) * self.width + column(x)];
+        let top = pixel(x0, y0) * (1.0 - tx) + pixel(x0 + 1.0, y0) * tx;
+        let bottom = pixel(x0, y0 + 1.0) * (1.0 - tx) + pixel(x0 + 1.0, y0 + 1.0) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+#[cfg(test)]
+mod background_tests {
+    use crate::features::{consts::WHITE, BLUE, RED};
+
+    use super::*;
+
+    #[test]
+    fn solid_background_ignores_direction() {
+        let b = Background::Color(RED);
+        assert_eq!(b.at(&Vector::new(0.0, 1.0, 0.0)), RED);
+        assert_eq!(b.at(&Vector::new(1.0, -1.0, 0.0)), RED);
+    }
+
+    #[test]
+    fn gradient_blends_by_elevation() {
+        let b = Background::Gradient(BLACK, WHITE);
+        assert_eq!(b.at(&Vector::new(0.0, -1.0, 0.0)), BLACK);
+        assert_eq!(b.at(&Vector::new(0.0, 1.0, 0.0)), WHITE);
+        assert_eq!(b.at(&Vector::new(1.0, 0.0, 0.0)), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn clear_sky_is_blue_away_from_sun() {
+        let sky = Sky::new(Vector::new(0.0, 1.0, 1.0), 2.5);
+        let c = sky.at(&Vector::new(0.0, 1.0, -1.0));
+        assert!(c.rgb.z > c.rgb.x);
+        assert!(c.rgb.x > 0.0);
+    }
+
+    #[test]
+    fn sky_is_brighter_towards_sun() {
+        let sky = Sky::new(Vector::new(0.0, 1.0, 1.0), 2.5);
+        let near = sky.at(&Vector::new(0.0, 1.0, 1.1));
+        let far = sky.at(&Vector::new(0.0, 1.0, -1.0));
+        assert!(near.rgb.y > far.rgb.y);
+    }
+
+    #[test]
+    fn environment_map_lookup() {
+        let map = EnvironmentMap {
+            width: 2,
+            height: 2,
+            pixels: vec![RED, RED, BLUE, BLUE],
+            path: None,
+        };
+        assert_eq!(map.at(&Vector::new(0.0, 1.0, 0.0)), RED);
+        assert_eq!(map.at(&Vector::new(0.0, -1.0, 0.0)), BLUE);
+    }
+}
@@ -1,10 +1,31 @@
-use std::f64::consts::PI;
+use std::{
+    f64::consts::PI,
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
 
 use super::{
-    canvas::Canvas, matrice::Matrice, point::Point, ray::Ray, view_transformation, world::World,
+    aov::{AovBuffers, AovSample},
+    canvas::Canvas,
+    color::Color,
+    film::{CancelToken, Film, PassStats},
+    filter::Filter,
+    matrice::Matrice,
+    point::Point,
+    ray::Ray,
+    sampling::random_in_unit_disk,
+    stats::{self, RayKind, RenderStats, TileTiming},
+    tile::{tiles, Checkpoint, Tile, TileOptions},
+    view_transformation,
+    world::{World, REMAINING},
     Vector,
 };
 use indicatif::ProgressBar;
+use rand::Rng;
 extern crate rayon;
 use rayon::prelude::*;
 
@@ -16,58 +37,364 @@ pub struct Camera {
     pub pixel_size: f64,
     pub half_width: f64,
     pub half_height: f64,
+    pub aperture: f64,
+    pub focal_distance: f64,
+    pub samples: usize,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub filter: Filter,
+    projection: Projection,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy, PartialOrd)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    // parallel rays covering the given view width
+    Orthographic(f64),
+    // equidistant fisheye, field_of_view spans the inscribed image circle
+    Fisheye,
+    // full 360 by 180 degree panorama
+    Equirectangular,
 }
 
-const REMAINING: usize = 4;
 impl Camera {
     pub fn new(hsize: f64, vsize: f64, field_of_view: f64) -> Self {
-        let half_width: f64;
-        let half_height: f64;
-        let half_view = (field_of_view / 2.0).tan();
-        let aspect = hsize / vsize;
-        if aspect >= 1.0 {
-            half_width = half_view;
-            half_height = half_view / aspect;
-        } else {
-            half_width = half_view * aspect;
-            half_height = half_view;
-        }
-
-        Camera {
+        let mut camera = Camera {
             hsize,
             vsize,
             field_of_view,
             transform: Matrice::identity(),
-            pixel_size: (half_width * 2.0) / hsize,
-            half_width,
-            half_height,
+            pixel_size: 0.0,
+            half_width: 0.0,
+            half_height: 0.0,
+            aperture: 0.0,
+            focal_distance: 1.0,
+            samples: 1,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            filter: Filter::default(),
+            projection: Projection::Perspective,
+        };
+        camera.set_projection(Projection::Perspective);
+        camera
+    }
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        let half_view = match projection {
+            Projection::Orthographic(view_width) => view_width / 2.0,
+            _ => (self.field_of_view / 2.0).tan(),
+        };
+        let aspect = self.hsize / self.vsize;
+        if aspect >= 1.0 {
+            self.half_width = half_view;
+            self.half_height = half_view / aspect;
+        } else {
+            self.half_width = half_view * aspect;
+            self.half_height = half_view;
         }
+        self.pixel_size = (self.half_width * 2.0) / self.hsize;
     }
     pub fn ray_for_pixel(&self, px: f64, py: f64) -> Ray {
         let xoffset = (px + 0.9) * self.pixel_size;
         let yoffset = (py + 0.9) * self.pixel_size;
         let world_x = self.half_width - xoffset;
         let world_y = self.half_height - yoffset;
-        let pixel = &self.transform.inverse() * &Point::new(world_x, world_y, -1.0);
-        let origin = &self.transform.inverse() * &Point::new(0.0, 0.0, 0.0);
-        let direction = (pixel - origin).normalize();
-        Ray::new(origin, direction)
+        let (origin, direction) = match self.projection {
+            Projection::Perspective => self.lens_ray(world_x, world_y),
+            Projection::Orthographic(_) => (
+                Point::new(world_x, world_y, 0.0),
+                Vector::new(0.0, 0.0, -1.0),
+            ),
+            Projection::Fisheye => {
+                let radius = world_x.hypot(world_y);
+                let theta =
+                    radius / self.half_width.min(self.half_height) * self.field_of_view / 2.0;
+                let phi = world_y.atan2(world_x);
+                (
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(
+                        theta.sin() * phi.cos(),
+                        theta.sin() * phi.sin(),
+                        -theta.cos(),
+                    ),
+                )
+            }
+            Projection::Equirectangular => {
+                let longitude = world_x / self.half_width * PI;
+                let latitude = world_y / self.half_height * PI / 2.0;
+                (
+                    Point::new(0.0, 0.0, 0.0),
+                    Vector::new(
+                        latitude.cos() * longitude.sin(),
+                        latitude.sin(),
+                        -latitude.cos() * longitude.cos(),
+                    ),
+                )
+            }
+        };
+        let inverse = self.transform.inverse();
+        Ray::new(&inverse * &origin, (&inverse * &direction).normalize()).with_time(self.ray_time())
     }
-    pub fn render(&self, world: &World) -> Canvas {
-        let mut image = Canvas::new(self.hsize as usize, self.vsize as usize);
-        let bar = ProgressBar::new((self.vsize * self.hsize) as u64);
-        image
-            .canvas
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(y, row)| {
-                row.par_iter_mut().enumerate().for_each(|(x, pixel)| {
+    // a moment within the shutter interval, on the scale of object motion
+    fn ray_time(&self) -> f64 {
+        if self.shutter_close > self.shutter_open {
+            rand::thread_rng().gen_range(self.shutter_open..self.shutter_close)
+        } else {
+            self.shutter_open
+        }
+    }
+    fn lens_ray(&self, world_x: f64, world_y: f64) -> (Point, Vector) {
+        if self.aperture > 0.0 {
+            // thin lens: rays leave a disk on the lens and converge on the focal plane
+            let (lens_x, lens_y) = random_in_unit_disk();
+            let focus = self.focal_distance;
+            let origin = Point::new(lens_x * self.aperture, lens_y * self.aperture, 0.0);
+            let target = Point::new(world_x * focus, world_y * focus, -focus);
+            (origin, target - origin)
+        } else {
+            (
+                Point::new(0.0, 0.0, 0.0),
+                Vector::new(world_x, world_y, -1.0),
+            )
+        }
+    }
+    // one sample per pixel and pass: the first pass goes through pixel
+    // centres, later ones are jittered across the pixel footprint
+    fn pass(&self, world: &World, pass: usize) -> Vec<Vec<Color>> {
+        (0..self.vsize as usize)
+            .into_par_iter()
+            .map(|y| {
+                let mut rng = rand::thread_rng();
+                (0..self.hsize as usize)
+                    .map(|x| {
+                        let (jx, jy) = match pass {
+                            0 => (0.0, 0.0),
+                            _ => (rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5)),
+                        };
+                        let ray = self.ray_for_pixel(x as f64 + jx, y as f64 + jy);
+                        stats::count_ray(RayKind::Primary);
+                        world.color_at(&ray, REMAINING)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+    // accumulates up to `passes` sample passes, handing the running average
+    // to `on_pass` after each one; stops early once `cancel` is set
+    pub fn render_progressive<F>(
+        &self,
+        world: &World,
+        passes: usize,
+        cancel: &CancelToken,
+        mut on_pass: F,
+    ) -> Canvas
+    where
+        F: FnMut(&Canvas, &PassStats),
+    {
+        let mut film = Film::new(self.hsize as usize, self.vsize as usize);
+        let start = Instant::now();
+        for pass in 0..passes {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let pass_start = Instant::now();
+            film.add_pass(&self.pass(world, pass));
+            let stats = PassStats {
+                pass: pass + 1,
+                samples_per_pixel: pass + 1,
+                pass_time: pass_start.elapsed(),
+                elapsed: start.elapsed(),
+            };
+            on_pass(&film.to_canvas(), &stats);
+        }
+        film.to_canvas()
+    }
+    // traces a primary ray, looking up its auxiliary values only when asked
+    fn trace(&self, world: &World, ray: &Ray, aovs: bool) -> (Color, Option<AovSample>) {
+        stats::count_ray(RayKind::Primary);
+        match aovs {
+            true => world.sample_at(ray, REMAINING),
+            false => (world.color_at(ray, REMAINING), None),
+        }
+    }
+    // Traces the samples of the pixels in `tile`. With several samples per
+    // pixel they are splatted through the reconstruction filter into a film
+    // reaching as far past the tile as the filter does, whose borders are
+    // merged with those of the neighbouring tiles. Auxiliary values, when
+    // asked for, come from the samples inside each pixel.
+    fn render_tile(
+        &self,
+        world: &World,
+        tile: &Tile,
+        aovs: bool,
+    ) -> (Film, (usize, usize), Vec<Option<AovSample>>) {
+        if self.samples <= 1 {
+            let mut film = Film::new(tile.width, tile.height);
+            let aovs = tile
+                .pixels()
+                .map(|(x, y)| {
                     let ray = self.ray_for_pixel(x as f64, y as f64);
-                    bar.inc(1);
-                    *pixel = world.color_at(&ray, REMAINING);
+                    let (color, aov) = self.trace(world, &ray, aovs);
+                    film.add(x - tile.x, y - tile.y, color, 1.0);
+                    aov
                 })
-            });
-        image
+                .collect();
+            return (film, (tile.x, tile.y), aovs);
+        }
+        let margin = (self.filter.radius() - 0.5).ceil().max(0.0) as usize;
+        let (x0, y0) = (tile.x.saturating_sub(margin), tile.y.saturating_sub(margin));
+        let x1 = (tile.x + tile.width + margin).min(self.hsize as usize);
+        let y1 = (tile.y + tile.height + margin).min(self.vsize as usize);
+        let mut film = Film::new(x1 - x0, y1 - y0);
+        let mut rng = rand::thread_rng();
+        let aovs = tile
+            .pixels()
+            .map(|(px, py)| {
+                let mut hits = vec![];
+                for _ in 0..self.samples {
+                    let (jx, jy) = (rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5));
+                    let ray = self.ray_for_pixel(px as f64 + jx, py as f64 + jy);
+                    let (color, aov) = self.trace(world, &ray, aovs);
+                    let (fx, fy) = ((px - x0) as f64 + jx, (py - y0) as f64 + jy);
+                    film.splat(fx, fy, color, &self.filter);
+                    hits.extend(aov);
+                }
+                AovSample::combine(&hits, self.samples)
+            })
+            .collect();
+        (film, (x0, y0), aovs)
+    }
+    pub fn render(&self, world: &World) -> Canvas {
+        self.render_tiles(world, &TileOptions::default())
+            .expect("Rendering without a checkpoint cannot fail")
+    }
+    // renders tile by tile in the requested order, resuming from and
+    // periodically saving to the checkpoint file when one is given
+    pub fn render_tiles(&self, world: &World, options: &TileOptions) -> io::Result<Canvas> {
+        Ok(self.render_with_stats(world, options)?.0)
+    }
+    // like `render_tiles`, also reporting what the render spent its time on;
+    // tiles restored from a checkpoint are not counted
+    pub fn render_with_stats(
+        &self,
+        world: &World,
+        options: &TileOptions,
+    ) -> io::Result<(Canvas, RenderStats)> {
+        let (image, _, report) = self.render_with_aovs(world, options)?;
+        Ok((image, report))
+    }
+    // like `render_with_stats`, also collecting the auxiliary passes from the
+    // same primary rays when `options.aovs` is set
+    pub fn render_with_aovs(
+        &self,
+        world: &World,
+        options: &TileOptions,
+    ) -> io::Result<(Canvas, Option<AovBuffers>, RenderStats)> {
+        let start = Instant::now();
+        let (width, height) = (self.hsize as usize, self.vsize as usize);
+        let mut image = Canvas::new(width, height);
+        let mut buffers = options.aovs.then(|| AovBuffers {
+            width,
+            height,
+            samples: vec![None; width * height],
+        });
+        let (checkpoint, done) = match &options.checkpoint {
+            Some(path) => {
+                let (checkpoint, done) =
+                    Checkpoint::open(path, &mut image, options.tile_size, options.scene_hash)?;
+                (Some(checkpoint), done)
+            }
+            None => (None, Vec::new()),
+        };
+        let pending: Vec<_> = tiles(width, height, options.tile_size, options.order)
+            .into_iter()
+            .filter(|tile| !done.contains(tile))
+            .collect();
+        let bar = match options.progress {
+            true => ProgressBar::new((width * height) as u64),
+            false => ProgressBar::hidden(),
+        };
+        bar.inc(
+            done.iter()
+                .map(|tile| (tile.width * tile.height) as u64)
+                .sum(),
+        );
+        if let Some(buffers) = &mut buffers {
+            // checkpoints only keep colors, so restored tiles fall back to
+            // rays through the pixel centres
+            for (x, y) in done.iter().flat_map(Tile::pixels) {
+                buffers.samples[y * width + x] =
+                    world.aov_at(&self.ray_for_pixel(x as f64, y as f64));
+            }
+        }
+        let next = AtomicUsize::new(0);
+        let state = Mutex::new((
+            image,
+            Film::new(width, height),
+            buffers,
+            checkpoint,
+            Instant::now(),
+            RenderStats::default(),
+        ));
+        let result = Mutex::new(Ok(()));
+        // workers pull tiles in order so the image fills in the requested pattern
+        rayon::scope(|scope| {
+            for _ in 0..rayon::current_num_threads() {
+                scope.spawn(|_| {
+                    while let Some(tile) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let tile_start = Instant::now();
+                        stats::take();
+                        let (tile_film, (x0, y0), aovs) =
+                            self.render_tile(world, tile, options.aovs);
+                        let counters = stats::take();
+                        let time = tile_start.elapsed();
+                        bar.inc((tile.width * tile.height) as u64);
+                        let mut state = state.lock().unwrap();
+                        let (image, film, buffers, checkpoint, saved, report) = &mut *state;
+                        report.add(&counters, REMAINING);
+                        report.tiles.push(TileTiming { tile: *tile, time });
+                        film.merge(&tile_film, x0, y0);
+                        // neighbours yet to finish still add to the pixels
+                        // along the border, which a checkpoint keeps as they
+                        // are now
+                        for (x, y) in tile.pixels() {
+                            image.canvas[y][x] = film.at(x, y);
+                        }
+                        if let Some(buffers) = buffers {
+                            for ((x, y), aov) in tile.pixels().zip(aovs) {
+                                buffers.samples[y * width + x] = aov;
+                            }
+                        }
+                        if let Some(checkpoint) = checkpoint {
+                            let mut saving = checkpoint.push(*tile, image);
+                            if saving.is_ok() && saved.elapsed() >= options.checkpoint_interval {
+                                saving = checkpoint.flush();
+                                *saved = Instant::now();
+                            }
+                            if let Err(e) = saving {
+                                *result.lock().unwrap() = Err(e);
+                                next.store(pending.len(), Ordering::Relaxed);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        result.into_inner().unwrap()?;
+        let (mut image, film, buffers, checkpoint, _, mut report) = state.into_inner().unwrap();
+        // with every tile in, the pixels along tile borders have all their samples
+        for (x, y) in pending.iter().flat_map(Tile::pixels) {
+            image.canvas[y][x] = film.at(x, y);
+        }
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.finish()?;
+        }
+        report.total_time = start.elapsed();
+        Ok((image, buffers, report))
     }
 }
 
@@ -151,6 +478,100 @@ mod ray_for_pixel_tests {
     }
 }
 
+#[cfg(test)]
+mod depth_of_field_tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn point_at_depth(ray: &Ray, depth: f64) -> Point {
+        ray.position((-depth - ray.origin.z()) / ray.direction.z())
+    }
+
+    #[test]
+    fn default_camera_is_a_pinhole() {
+        let c = Camera::new(160.0, 120.0, PI / 2.0);
+        assert_eq!(c.aperture, 0.0);
+        assert_eq!(c.samples, 1);
+    }
+
+    #[test]
+    fn lens_rays_converge_on_focal_plane() {
+        let mut c = Camera::new(101.0, 101.0, PI / 2.0);
+        let pinhole = c.ray_for_pixel(20.0, 70.0);
+        c.aperture = 0.5;
+        c.focal_distance = 4.0;
+        let focus = point_at_depth(&pinhole, 4.0);
+        for _ in 0..10 {
+            let r = c.ray_for_pixel(20.0, 70.0);
+            assert_eq!(r.origin.z(), 0.0);
+            assert!(r.origin.x().hypot(r.origin.y()) < 0.5);
+            assert_eq!(point_at_depth(&r, 4.0), focus);
+        }
+    }
+}
+
+#[cfg(test)]
+mod shutter_tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn rays_are_timed_within_shutter_interval() {
+        let mut c = Camera::new(11.0, 11.0, PI / 2.0);
+        assert_eq!(c.ray_for_pixel(5.0, 5.0).time, 0.0);
+        c.shutter_open = 0.25;
+        c.shutter_close = 0.75;
+        for _ in 0..20 {
+            let time = c.ray_for_pixel(5.0, 5.0).time;
+            assert!((0.25..0.75).contains(&time));
+        }
+    }
+}
+
+#[cfg(test)]
+mod projection_tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let mut c = Camera::new(100.0, 50.0, PI / 2.0);
+        c.set_projection(Projection::Orthographic(10.0));
+        assert_eq!(c.pixel_size, 0.1);
+        let r1 = c.ray_for_pixel(10.0, 10.0);
+        let r2 = c.ray_for_pixel(60.0, 30.0);
+        assert_eq!(r1.direction, Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(r2.direction, Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(r1.origin - r2.origin, Vector::new(5.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn fisheye_angle_grows_linearly_with_radius() {
+        let mut c = Camera::new(101.0, 101.0, PI / 2.0);
+        let perspective = c.ray_for_pixel(10.0, 30.0).direction;
+        c.set_projection(Projection::Fisheye);
+        let fisheye = c.ray_for_pixel(10.0, 30.0).direction;
+        let radius = perspective.z().acos().tan().abs();
+        let theta = (-fisheye.z()).acos();
+        assert!((theta - radius * PI / 4.0).abs() < 1e-9);
+        assert!((fisheye.x() / fisheye.y() - perspective.x() / perspective.y()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn equirectangular_spans_full_circle() {
+        let mut c = Camera::new(200.0, 100.0, PI / 2.0);
+        c.set_projection(Projection::Equirectangular);
+        let r1 = c.ray_for_pixel(20.0, 30.0).direction;
+        let r2 = c.ray_for_pixel(120.0, 30.0).direction;
+        assert_eq!(r1.y(), r2.y());
+        assert!((r1.x() + r2.x()).abs() < 1e-9);
+        assert!((r1.z() + r2.z()).abs() < 1e-9);
+    }
+}
+
 #[cfg(test)]
 mod render_tests {
     use std::f64::consts::PI;
@@ -170,3 +591,171 @@ mod render_tests {
         assert_eq!(image.canvas[5][5], Color::new(0.38066, 0.47583, 0.2855));
     }
 }
+
+#[cfg(test)]
+mod progressive_tests {
+    use crate::features::{film::CancelToken, transformations::view_transformation, Point, Vector};
+
+    use super::*;
+
+    fn camera() -> Camera {
+        let mut c = Camera::new(5.0, 5.0, PI / 2.0);
+        c.transform = view_transformation(
+            Point::new(0.0, 0.0, -5.0),
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        c
+    }
+
+    #[test]
+    fn progressive_render_reports_every_pass() {
+        let w = World::default();
+        let mut seen = Vec::new();
+        let image = camera().render_progressive(&w, 3, &CancelToken::new(), |canvas, stats| {
+            assert_eq!(canvas.width, 5);
+            seen.push(stats.samples_per_pixel);
+        });
+        assert_eq!(seen, vec![1, 2, 3]);
+        assert_eq!(image.height, 5);
+    }
+
+    #[test]
+    fn first_pass_matches_pixel_centres() {
+        let w = World::default();
+        let c = camera();
+        let mut first = None;
+        c.render_progressive(&w, 1, &CancelToken::new(), |canvas, _| {
+            first = Some(canvas.canvas[2][2]);
+        });
+        assert_eq!(
+            first,
+            Some(w.color_at(&c.ray_for_pixel(2.0, 2.0), REMAINING))
+        );
+    }
+
+    #[test]
+    fn cancelling_stops_after_the_current_pass() {
+        let w = World::default();
+        let cancel = CancelToken::new();
+        let mut passes = 0;
+        camera().render_progressive(&w, 10, &cancel, |_, _| {
+            passes += 1;
+            cancel.cancel();
+        });
+        assert_eq!(passes, 1);
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use crate::features::{transformations::view_transformation, Point, Vector};
+
+    use super::*;
+
+    #[test]
+    fn render_reports_rays_and_tests() {
+        let w = World::default();
+        let mut c = Camera::new(6.0, 4.0, PI / 2.0);
+        c.transform = view_transformation(
+            Point::new(0.0, 0.0, -5.0),
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        let options = TileOptions {
+            tile_size: 2,
+            ..Default::default()
+        };
+        let (_, report) = c.render_with_stats(&w, &options).unwrap();
+        assert_eq!(report.rays.primary, 24);
+        assert_eq!(report.depth_histogram[0], 24);
+        assert_eq!(report.tiles.len(), 6);
+        // every primary and shadow ray is tested against both spheres
+        assert_eq!(
+            report.intersection_tests.sphere,
+            2 * (report.rays.primary + report.rays.shadow)
+        );
+        assert_eq!(report.intersection_tests.plane, 0);
+    }
+
+    #[test]
+    fn aovs_come_from_the_image_rays() {
+        let w = World::default();
+        let mut c = Camera::new(12.0, 8.0, PI / 2.0);
+        c.transform = view_transformation(
+            Point::new(0.0, 0.0, -2.5),
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        c.samples = 4;
+        let options = TileOptions {
+            tile_size: 2,
+            aovs: true,
+            ..Default::default()
+        };
+        let (_, buffers, report) = c.render_with_aovs(&w, &options).unwrap();
+        assert_eq!(report.rays.primary, 12 * 8 * 4);
+        let buffers = buffers.unwrap();
+        // every jittered sample of the centre pixel lands on the sphere
+        let centre = buffers.at(6, 4).unwrap();
+        assert_eq!(centre.object, 0);
+        // between the front of the sphere and where rays graze it
+        assert!(centre.depth > 1.5 && centre.depth < 2.3);
+        assert_eq!(buffers.at(0, 0), None);
+    }
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use crate::features::{transformations::view_transformation, Background, Point, Vector, GREY};
+
+    use super::*;
+
+    fn camera(filter: Filter) -> Camera {
+        let mut c = Camera::new(6.0, 6.0, PI / 2.0);
+        c.transform = view_transformation(
+            Point::new(0.0, 0.0, -5.0),
+            Point::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        c.samples = 4;
+        c.filter = filter;
+        c
+    }
+
+    #[test]
+    fn constant_scene_is_unchanged_by_filters() {
+        let mut w = World::new(Default::default());
+        w.background = Background::Color(GREY);
+        for filter in [
+            Filter::Tent(1.5),
+            Filter::Mitchell(2.0),
+            Filter::Lanczos(2.0),
+        ] {
+            let c = camera(filter);
+            for tile_size in [1, 4] {
+                let options = TileOptions {
+                    tile_size,
+                    ..Default::default()
+                };
+                let image = c.render_tiles(&w, &options).unwrap();
+                for pixel in image.canvas.iter().flatten() {
+                    assert!((pixel.rgb.x - GREY.rgb.x).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wide_filters_trace_each_sample_once() {
+        let w = World::new(Default::default());
+        let options = TileOptions {
+            tile_size: 3,
+            ..Default::default()
+        };
+        for filter in [Filter::default(), Filter::Tent(1.5), Filter::Mitchell(2.0)] {
+            let (_, report) = camera(filter).render_with_stats(&w, &options).unwrap();
+            assert_eq!(report.rays.primary, 6 * 6 * 4);
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use std::{fs, path::Path};
+
+use image::{ImageResult, RgbImage};
+
 use super::{color::Color, consts::BLACK};
 
 pub struct Canvas {
@@ -24,4 +28,36 @@ impl Canvas {
         }
         content
     }
+
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let rgb = self.canvas[y as usize][x as usize].clamp().rgb;
+            image::Rgb([rgb.x as u8, rgb.y as u8, rgb.z as u8])
+        })
+    }
+
+    // the image format follows the file extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|ext| ext == "ppm") {
+            return Ok(fs::write(path, self.to_ppm())?);
+        }
+        self.to_image().save(path)
+    }
+}
+
+#[cfg(test)]
+mod canvas_tests {
+    use crate::features::RED;
+
+    use super::*;
+
+    #[test]
+    fn image_matches_ppm_values() {
+        let mut c = Canvas::new(2, 1);
+        c.canvas[0][1] = RED;
+        let image = c.to_image();
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(image.get_pixel(1, 0).0, [255, 0, 0]);
+    }
 }
@@ -1,5 +1,10 @@
 use super::{
-    consts::EPSILON, intersection::Intersection, object::Object, point::Point, ray::Ray,
+    color::Color,
+    consts::{EPSILON, WHITE},
+    intersection::Intersection,
+    object::Object,
+    point::Point,
+    ray::Ray,
     vector::Vector,
 };
 
@@ -15,18 +20,20 @@ pub struct Computation<'a> {
     pub reflectv: Vector,
     pub n1: f64,
     pub n2: f64,
+    pub medium: Option<&'a Object>,
+    pub next_medium: Option<&'a Object>,
+    pub wavelength: Option<f64>,
+    pub time: f64,
 }
 
 impl<'a> Computation<'a> {
     pub fn new(ray: &Ray, i: &Intersection<'a>, xs: &[Intersection<'a>]) -> Self {
-        let mut n1: f64 = 1.0;
-        let mut n2: f64 = 1.0;
+        let mut medium = None;
+        let mut next_medium = None;
         let mut containers: Vec<&Object> = vec![];
         for x in xs.iter() {
             if *i == *x {
-                if let Some(l) = containers.last() {
-                    n1 = l.material.refractive_index;
-                }
+                medium = containers.last().copied();
             }
             if let Some(index) = containers.iter().position(|a| *a == x.object) {
                 containers.remove(index);
@@ -34,15 +41,16 @@ impl<'a> Computation<'a> {
                 containers.push(x.object)
             }
             if *i == *x {
-                if let Some(l) = containers.last() {
-                    n2 = l.material.refractive_index;
-                }
+                next_medium = containers.last().copied();
                 break;
             }
         }
+        let index_of = |medium: Option<&Object>| {
+            medium.map_or(1.0, |m| m.material.refractive_index_at(ray.wavelength))
+        };
         let point = ray.position(i.t);
         let eyev = -ray.direction;
-        let mut normalv = i.object.normal_at(&point);
+        let mut normalv = i.object.normal_at(&point, ray.time);
         let inside: bool;
         if normalv.dot_product(&eyev) < 0.0 {
             inside = true;
@@ -60,8 +68,33 @@ impl<'a> Computation<'a> {
             normalv,
             inside,
             reflectv: ray.direction.reflect(&normalv),
-            n1,
-            n2,
+            n1: index_of(medium),
+            n2: index_of(next_medium),
+            medium,
+            next_medium,
+            wavelength: ray.wavelength,
+            time: ray.time,
+        }
+    }
+    // secondary rays keep the wavelength and time of the ray that produced this hit
+    pub fn spawn_ray(&self, origin: Point, direction: Vector) -> Ray {
+        Ray::new(origin, direction)
+            .with_wavelength(self.wavelength)
+            .with_time(self.time)
+    }
+    pub fn is_dispersive(&self) -> bool {
+        [self.medium, self.next_medium]
+            .iter()
+            .flatten()
+            .any(|m| m.material.is_dispersive())
+    }
+    // light reaching the eye from this hit has crossed the medium the ray was in
+    pub fn transmittance(&self) -> Color {
+        match self.medium {
+            Some(medium) => medium
+                .material
+                .transmittance(self.t * self.eyev.magnitude()),
+            None => WHITE,
         }
     }
     pub fn shlick(&self) -> f64 {
@@ -75,14 +108,20 @@ impl<'a> Computation<'a> {
             cos = (1.0 - sin2_t).sqrt();
         }
         let r0 = ((self.n1 - self.n2) / (self.n1 + self.n2)).powi(2);
-        r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+        schlick(r0, cos)
     }
 }
 
+/// Schlick's approximation of the Fresnel reflectance for a surface whose
+/// reflectance at normal incidence is `r0`, seen at an angle with cosine `cos`.
+pub fn schlick(r0: f64, cos: f64) -> f64 {
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
 #[cfg(test)]
 mod computation_tests {
 
-    use crate::features::Material;
+    use crate::features::{Dispersion, Material};
 
     use super::*;
     #[test]
@@ -173,6 +212,40 @@ mod computation_tests {
         assert_eq!(reflectance, 0.4887308101221217);
     }
 
+    #[test]
+    fn medium_is_the_object_the_ray_travels_through() {
+        let shape = glass_sphere();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let xs = [
+            Intersection::new(4.0, &shape),
+            Intersection::new(6.0, &shape),
+        ];
+        let comps = Computation::new(&r, &xs[0], &xs);
+        assert_eq!(comps.medium, None);
+        let comps = Computation::new(&r, &xs[1], &xs);
+        assert_eq!(comps.medium, Some(&shape));
+    }
+
+    #[test]
+    fn refractive_indices_follow_ray_wavelength() {
+        let mut shape = glass_sphere();
+        shape.material.dispersion = Some(Dispersion::Cauchy(1.5, 0.01));
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0))
+            .with_wavelength(Some(1000.0));
+        let xs = [
+            Intersection::new(4.0, &shape),
+            Intersection::new(6.0, &shape),
+        ];
+        let comps = Computation::new(&r, &xs[0], &xs);
+        assert!(comps.is_dispersive());
+        assert_eq!(comps.n1, 1.0);
+        assert_eq!(comps.n2, 1.51);
+        assert_eq!(
+            comps.spawn_ray(comps.point, comps.reflectv).wavelength,
+            Some(1000.0)
+        );
+    }
+
     fn glass_sphere() -> Object {
         let m = Material {
             transparency: 1.0,
@@ -0,0 +1,139 @@
+use rayon::prelude::*;
+
+use super::{aov::AovBuffers, canvas::Canvas, color::Color, consts::BLACK};
+
+// joint bilateral filter: neighbours are averaged only where the auxiliary
+// buffers agree, so noise is smoothed without blurring across edges
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denoiser {
+    pub radius: usize,
+    pub sigma_spatial: f64,
+    pub sigma_color: f64,
+    pub sigma_normal: f64,
+    pub sigma_albedo: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            radius: 3,
+            sigma_spatial: 2.0,
+            sigma_color: 0.3,
+            sigma_normal: 0.2,
+            sigma_albedo: 0.1,
+        }
+    }
+}
+
+impl Denoiser {
+    pub fn apply(&self, image: &Canvas, guide: &AovBuffers) -> Canvas {
+        let mut output = Canvas::new(image.width, image.height);
+        output
+            .canvas
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    *pixel = self.filter_pixel(image, guide, x, y);
+                }
+            });
+        output
+    }
+
+    fn filter_pixel(&self, image: &Canvas, guide: &AovBuffers, x: usize, y: usize) -> Color {
+        let centre = image.canvas[y][x];
+        let centre_aov = guide.at(x, y);
+        let r = self.radius as isize;
+        let mut sum = BLACK;
+        let mut total = 0.0;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                if nx < 0 || ny < 0 || nx >= image.width as isize || ny >= image.height as isize {
+                    continue;
+                }
+                let (nx, ny) = (nx as usize, ny as usize);
+                let color = image.canvas[ny][nx];
+                let mut exponent = (dx * dx + dy * dy) as f64 / self.sigma_spatial.powi(2)
+                    + distance2(centre, color) / self.sigma_color.powi(2);
+                match (centre_aov, guide.at(nx, ny)) {
+                    (Some(a), Some(b)) => {
+                        exponent += (a.normal - b.normal).magnitude().powi(2)
+                            / self.sigma_normal.powi(2)
+                            + distance2(a.albedo, b.albedo) / self.sigma_albedo.powi(2);
+                    }
+                    (None, None) => {}
+                    // never mix background into surfaces or the other way round
+                    _ => continue,
+                }
+                let weight = (-0.5 * exponent).exp();
+                sum = sum + color * weight;
+                total += weight;
+            }
+        }
+        sum * (1.0 / total)
+    }
+}
+
+// mean squared difference per channel
+fn distance2(a: Color, b: Color) -> f64 {
+    let d = a - b;
+    (d.rgb.x.powi(2) + d.rgb.y.powi(2) + d.rgb.z.powi(2)) / 3.0
+}
+
+#[cfg(test)]
+mod denoise_tests {
+    use crate::features::{AovSample, Vector, RED, WHITE};
+
+    use super::*;
+
+    fn guide(width: usize, height: usize, normal: impl Fn(usize) -> Vector) -> AovBuffers {
+        AovBuffers {
+            width,
+            height,
+            samples: (0..width * height)
+                .map(|i| {
+                    Some(AovSample {
+                        depth: 1.0,
+                        normal: normal(i % width),
+                        albedo: RED,
+                        object: 0,
+                        shadowed: false,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn noise_on_a_flat_surface_is_smoothed() {
+        let mut image = Canvas::new(9, 9);
+        for (y, row) in image.canvas.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                let v = if (x + y) % 2 == 0 { 0.4 } else { 0.6 };
+                *pixel = Color::new(v, v, v);
+            }
+        }
+        let flat = guide(9, 9, |_| Vector::new(0.0, 0.0, -1.0));
+        let result = Denoiser::default().apply(&image, &flat);
+        let v = result.canvas[4][4].rgb.x;
+        assert!((v - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn edges_in_the_guide_are_preserved() {
+        let mut image = Canvas::new(8, 4);
+        for row in image.canvas.iter_mut() {
+            for pixel in row.iter_mut().skip(4) {
+                *pixel = WHITE;
+            }
+        }
+        let folded = guide(8, 4, |x| match x < 4 {
+            true => Vector::new(0.0, 0.0, -1.0),
+            false => Vector::new(1.0, 0.0, 0.0),
+        });
+        let result = Denoiser::default().apply(&image, &folded);
+        assert!(result.canvas[1][3].rgb.x < 0.01);
+        assert!(result.canvas[1][4].rgb.x > 0.99);
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::{
+    canvas::Canvas,
+    color::Color,
+    consts::{BLACK, EPSILON},
+    filter::Filter,
+};
+
+// float accumulation buffer: every pixel keeps a weighted sum of its samples
+// so passes can be added indefinitely and averaged at any point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    sum: Vec<Color>,
+    weight: Vec<f64>,
+    // plain sum and count of the samples taken inside each pixel, for when
+    // negative filter lobes cancel out its weight
+    own_sum: Vec<Color>,
+    own_count: Vec<usize>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Film {
+            width,
+            height,
+            sum: vec![BLACK; width * height],
+            weight: vec![0.0; width * height],
+            own_sum: vec![BLACK; width * height],
+            own_count: vec![0; width * height],
+        }
+    }
+
+    pub fn add(&mut self, x: usize, y: usize, color: Color, weight: f64) {
+        let i = y * self.width + x;
+        self.sum[i] = self.sum[i] + color * weight;
+        self.weight[i] += weight;
+    }
+
+    // spreads a sample taken at (x, y), pixel centres being at whole
+    // coordinates, over every pixel within the filter's radius
+    pub fn splat(&mut self, x: f64, y: f64, color: Color, filter: &Filter) {
+        let r = filter.radius();
+        let (min_x, max_x) = ((x - r).ceil().max(0.0), (x + r).floor());
+        let (min_y, max_y) = ((y - r).ceil().max(0.0), (y + r).floor());
+        for py in min_y as usize..=(max_y as usize).min(self.height - 1) {
+            for px in min_x as usize..=(max_x as usize).min(self.width - 1) {
+                let weight = filter.weight(px as f64 - x, py as f64 - y);
+                if weight != 0.0 {
+                    self.add(px, py, color, weight);
+                }
+            }
+        }
+        // a pixel owns the samples from half a pixel before its centre up to
+        // half a pixel after it
+        let (px, py) = ((x + 0.5).floor(), (y + 0.5).floor());
+        if px >= 0.0 && py >= 0.0 && (px as usize) < self.width && (py as usize) < self.height {
+            let i = py as usize * self.width + px as usize;
+            self.own_sum[i] = self.own_sum[i] + color;
+            self.own_count[i] += 1;
+        }
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> Color {
+        let i = y * self.width + x;
+        if self.weight[i] > EPSILON {
+            self.sum[i] * (1.0 / self.weight[i])
+        } else if self.own_count[i] > 0 {
+            self.own_sum[i] * (1.0 / self.own_count[i] as f64)
+        } else {
+            BLACK
+        }
+    }
+
+    // adds in the samples of a smaller film whose top left pixel lies at
+    // (x, y) in this one
+    pub fn merge(&mut self, other: &Film, x: usize, y: usize) {
+        for oy in 0..other.height {
+            for ox in 0..other.width {
+                let (i, j) = ((y + oy) * self.width + x + ox, oy * other.width + ox);
+                self.sum[i] = self.sum[i] + other.sum[j];
+                self.weight[i] += other.weight[j];
+                self.own_sum[i] = self.own_sum[i] + other.own_sum[j];
+                self.own_count[i] += other.own_count[j];
+            }
+        }
+    }
+
+    pub fn add_pass(&mut self, pass: &[Vec<Color>]) {
+        for (y, row) in pass.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                self.add(x, y, *color, 1.0);
+            }
+        }
+    }
+
+    pub fn to_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.width, self.height);
+        for (y, row) in canvas.canvas.iter_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = self.at(x, y);
+            }
+        }
+        canvas
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PassStats {
+    pub pass: usize,
+    pub samples_per_pixel: usize,
+    pub pass_time: Duration,
+    pub elapsed: Duration,
+}
+
+// shared flag a caller can set from any thread to stop a render early
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod film_tests {
+    use crate::features::{BLACK, RED, WHITE};
+
+    use super::*;
+
+    #[test]
+    fn film_averages_passes() {
+        let mut film = Film::new(2, 1);
+        film.add_pass(&[vec![WHITE, RED]]);
+        film.add_pass(&[vec![BLACK, RED]]);
+        let canvas = film.to_canvas();
+        assert_eq!(canvas.canvas[0][0], Color::new(0.5, 0.5, 0.5));
+        assert_eq!(canvas.canvas[0][1], RED);
+    }
+
+    #[test]
+    fn film_weights_samples() {
+        let mut film = Film::new(1, 1);
+        film.add(0, 0, WHITE, 3.0);
+        film.add(0, 0, BLACK, 1.0);
+        assert_eq!(film.to_canvas().canvas[0][0], Color::new(0.75, 0.75, 0.75));
+    }
+
+    #[test]
+    fn box_splat_stays_in_its_pixel() {
+        let mut film = Film::new(3, 1);
+        film.splat(1.3, 0.0, RED, &Filter::default());
+        let canvas = film.to_canvas();
+        assert_eq!(canvas.canvas[0], vec![BLACK, RED, BLACK]);
+    }
+
+    #[test]
+    fn wide_splat_reaches_neighbours() {
+        let mut film = Film::new(3, 1);
+        film.splat(1.0, 0.0, WHITE, &Filter::Tent(1.5));
+        film.splat(0.0, 0.0, BLACK, &Filter::Tent(1.5));
+        // the middle pixel weighs its own sample 1.5 against 0.5 from the left
+        assert_eq!(film.at(1, 0), Color::new(0.75, 0.75, 0.75));
+        assert_eq!(film.at(2, 0), WHITE);
+    }
+
+    #[test]
+    fn merged_films_add_up() {
+        let filter = Filter::Tent(1.0);
+        let mut whole = Film::new(4, 1);
+        whole.splat(1.2, 0.0, WHITE, &filter);
+        whole.splat(2.3, 0.0, BLACK, &filter);
+        let (mut left, mut right) = (Film::new(3, 1), Film::new(3, 1));
+        left.splat(1.2, 0.0, WHITE, &filter);
+        right.splat(1.3, 0.0, BLACK, &filter);
+        let mut merged = Film::new(4, 1);
+        merged.merge(&left, 0, 0);
+        merged.merge(&right, 1, 0);
+        for x in 0..4 {
+            assert_eq!(merged.at(x, 0), whole.at(x, 0));
+        }
+    }
+
+    #[test]
+    fn cancelled_out_weights_fall_back_to_own_samples() {
+        let mut film = Film::new(3, 1);
+        film.splat(1.45, 0.45, WHITE, &Filter::Lanczos(2.0));
+        // enough neighbours in the negative lobe to outweigh it
+        for _ in 0..6 {
+            film.splat(2.45, 0.0, BLACK, &Filter::Lanczos(2.0));
+        }
+        assert_eq!(film.at(1, 0), WHITE);
+    }
+
+    #[test]
+    fn cancel_token_is_shared_between_clones() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+}
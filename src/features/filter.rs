@@ -0,0 +1,121 @@
+use std::f64::consts::PI;
+
+// pixel reconstruction filters, each with its radius in pixels; weights are
+// separable, w(x, y) = w(x) * w(y)
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Filter {
+    Box(f64),
+    Tent(f64),
+    Gaussian(f64),
+    // Mitchell-Netravali with B = C = 1/3
+    Mitchell(f64),
+    // windowed sinc with as many lobes as the radius
+    Lanczos(f64),
+}
+
+impl Default for Filter {
+    // a box one pixel wide is the plain average of the pixel's own samples
+    fn default() -> Self {
+        Filter::Box(0.5)
+    }
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box(r)
+            | Filter::Tent(r)
+            | Filter::Gaussian(r)
+            | Filter::Mitchell(r)
+            | Filter::Lanczos(r) => r,
+        }
+    }
+
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        let r = self.radius();
+        if x >= r {
+            return 0.0;
+        }
+        match self {
+            Filter::Box(_) => 1.0,
+            Filter::Tent(_) => r - x,
+            Filter::Gaussian(_) => {
+                let gaussian = |x: f64| (-2.0 * (x / r).powi(2)).exp();
+                gaussian(x) - gaussian(r)
+            }
+            Filter::Mitchell(_) => mitchell(2.0 * x / r),
+            Filter::Lanczos(_) => sinc(x) * sinc(x / r),
+        }
+    }
+}
+
+fn mitchell(x: f64) -> f64 {
+    let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+    let value = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b)
+    } else {
+        (-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    };
+    value / 6.0
+}
+
+fn sinc(x: f64) -> f64 {
+    if x < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::*;
+
+    const FILTERS: [Filter; 5] = [
+        Filter::Box(1.5),
+        Filter::Tent(1.5),
+        Filter::Gaussian(1.5),
+        Filter::Mitchell(2.0),
+        Filter::Lanczos(3.0),
+    ];
+
+    #[test]
+    fn filters_vanish_outside_their_radius() {
+        for filter in FILTERS {
+            let r = filter.radius();
+            assert!(filter.weight(0.0, 0.0) > 0.0);
+            assert_eq!(filter.weight(r, 0.0), 0.0);
+            assert_eq!(filter.weight(0.0, -r - 0.1), 0.0);
+        }
+    }
+
+    #[test]
+    fn filters_are_symmetric_and_peak_at_the_centre() {
+        for filter in FILTERS {
+            assert_eq!(filter.weight(0.3, 0.7), filter.weight(-0.3, -0.7));
+            assert!(filter.weight(0.0, 0.0) >= filter.weight(0.4, 0.0));
+        }
+    }
+
+    #[test]
+    fn mitchell_and_lanczos_have_negative_lobes() {
+        assert!(Filter::Mitchell(2.0).weight(1.5, 0.0) < 0.0);
+        assert!(Filter::Lanczos(3.0).weight(1.5, 0.0) < 0.0);
+    }
+
+    #[test]
+    fn default_box_covers_a_single_pixel() {
+        let filter = Filter::default();
+        assert_eq!(filter.weight(0.49, -0.49), 1.0);
+        assert_eq!(filter.weight(0.5, 0.0), 0.0);
+    }
+}
@@ -1,7 +1,11 @@
+use std::f64::consts::PI;
+
 use super::{
     color::Color,
+    computation::schlick,
     consts::{BLACK, WHITE},
     light::Light,
+    media::Volume,
     object::Object,
     pattern::Pattern,
     point::Point,
@@ -19,8 +23,42 @@ pub struct Material {
     pub reflective: f64,
     pub transparency: f64,
     pub refractive_index: f64,
+    pub shading: Shading,
+    pub roughness: f64,
+    pub metallic: f64,
+    pub roughness_map: Option<Pattern>,
+    pub metallic_map: Option<Pattern>,
+    pub absorption: Color,
+    pub absorption_density: f64,
+    pub dispersion: Option<Dispersion>,
+    pub volume: Option<Volume>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+pub enum Dispersion {
+    // Abbe number V_d; the refractive index is taken as n_d
+    Abbe(f64),
+    // Cauchy coefficients A and B, with B in square micrometres
+    Cauchy(f64, f64),
+}
+
+// Fraunhofer d, F and C lines in nanometres, used to define the Abbe number
+const WAVELENGTH_D: f64 = 587.6;
+const WAVELENGTH_F: f64 = 486.1;
+const WAVELENGTH_C: f64 = 656.3;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, PartialOrd)]
+pub enum Shading {
+    #[default]
+    Phong,
+    Microfacet,
 }
 
+// roughness below this makes the GGX lobe a numerical spike
+const MIN_ROUGHNESS: f64 = 0.03;
+// reflectance at normal incidence of a dielectric without a refractive index
+const DIELECTRIC_F0: f64 = 0.04;
+
 impl Material {
     pub fn builder() -> MaterialBuilder {
         MaterialBuilder::default()
@@ -34,11 +72,78 @@ impl Material {
         normalv: &Vector,
         in_shadow: bool,
     ) -> Color {
-        let color = match &self.pattern {
+        let color = self.color_at(object, point);
+        match self.shading {
+            Shading::Phong => self.phong(color, light, point, eyev, normalv, in_shadow),
+            Shading::Microfacet => {
+                self.microfacet(color, light, object, point, eyev, normalv, in_shadow)
+            }
+        }
+    }
+
+    pub fn color_at(&self, object: &Object, point: &Point) -> Color {
+        match &self.pattern {
             Some(p) => p.at(object, point),
             None => self.color,
-        };
+        }
+    }
+
+    pub fn roughness_at(&self, object: &Object, point: &Point) -> f64 {
+        match &self.roughness_map {
+            Some(map) => map.at(object, point).rgb.x,
+            None => self.roughness,
+        }
+    }
+
+    pub fn metallic_at(&self, object: &Object, point: &Point) -> f64 {
+        match &self.metallic_map {
+            Some(map) => map.at(object, point).rgb.x,
+            None => self.metallic,
+        }
+    }
 
+    pub fn refractive_index_at(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(Dispersion::Abbe(abbe)), Some(wavelength)) if abbe > 0.0 => {
+                let b = (self.refractive_index - 1.0)
+                    / (abbe * (inverse_square_um(WAVELENGTH_F) - inverse_square_um(WAVELENGTH_C)));
+                let a = self.refractive_index - b * inverse_square_um(WAVELENGTH_D);
+                a + b * inverse_square_um(wavelength)
+            }
+            (Some(Dispersion::Cauchy(a, b)), Some(wavelength)) => {
+                a + b * inverse_square_um(wavelength)
+            }
+            _ => self.refractive_index,
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+
+    // Beer-Lambert attenuation of light that travelled `distance` inside the
+    // material. `absorption` is the tint left after one unit at density 1.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        if self.absorption_density == 0.0 {
+            return WHITE;
+        }
+        let depth = self.absorption_density * distance;
+        Color::new(
+            self.absorption.rgb.x.powf(depth),
+            self.absorption.rgb.y.powf(depth),
+            self.absorption.rgb.z.powf(depth),
+        )
+    }
+
+    fn phong(
+        &self,
+        color: Color,
+        light: &Light,
+        point: &Point,
+        eyev: &Vector,
+        normalv: &Vector,
+        in_shadow: bool,
+    ) -> Color {
         let effective_color = color * light.intensity;
         let ambient = effective_color * self.ambient;
         if in_shadow {
@@ -65,6 +170,74 @@ impl Material {
 
         ambient + diffuse + specular
     }
+
+    // Cook-Torrance with a GGX distribution, Smith-Schlick geometry term and
+    // Schlick Fresnel. The light intensity is treated as irradiance scaled by PI
+    // so a white Lambertian surface matches the Phong diffuse term.
+    #[allow(clippy::too_many_arguments)]
+    fn microfacet(
+        &self,
+        color: Color,
+        light: &Light,
+        object: &Object,
+        point: &Point,
+        eyev: &Vector,
+        normalv: &Vector,
+        in_shadow: bool,
+    ) -> Color {
+        let ambient = color * light.intensity * self.ambient;
+        if in_shadow {
+            return ambient;
+        }
+        let lightv = (light.position - *point).normalize();
+        let n_dot_l = lightv.dot_product(normalv);
+        let n_dot_v = eyev.dot_product(normalv);
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
+            return ambient;
+        }
+        let roughness = self.roughness_at(object, point).clamp(MIN_ROUGHNESS, 1.0);
+        let metallic = self.metallic_at(object, point).clamp(0.0, 1.0);
+        let halfv = (lightv + *eyev).normalize();
+        let n_dot_h = normalv.dot_product(&halfv).max(0.0);
+        let v_dot_h = eyev.dot_product(&halfv).max(0.0);
+
+        let f0 = self.dielectric_f0();
+        let f0 = Color::new(f0, f0, f0) * (1.0 - metallic) + color * metallic;
+        let fresnel = Color::new(
+            schlick(f0.rgb.x, v_dot_h),
+            schlick(f0.rgb.y, v_dot_h),
+            schlick(f0.rgb.z, v_dot_h),
+        );
+        let distribution = ggx_distribution(n_dot_h, roughness);
+        let geometry = smith_geometry(n_dot_v, roughness) * smith_geometry(n_dot_l, roughness);
+        let specular = fresnel * (distribution * geometry / (4.0 * n_dot_l * n_dot_v));
+        let diffuse = (WHITE - fresnel) * (1.0 - metallic) * color * (1.0 / PI);
+
+        ambient + (diffuse + specular) * light.intensity * (n_dot_l * PI)
+    }
+
+    fn dielectric_f0(&self) -> f64 {
+        if self.refractive_index > 1.0 {
+            ((self.refractive_index - 1.0) / (self.refractive_index + 1.0)).powi(2)
+        } else {
+            DIELECTRIC_F0
+        }
+    }
+}
+
+fn inverse_square_um(wavelength_nm: f64) -> f64 {
+    (1000.0 / wavelength_nm).powi(2)
+}
+
+fn ggx_distribution(n_dot_h: f64, roughness: f64) -> f64 {
+    let a2 = roughness.powi(4);
+    let denominator = n_dot_h.powi(2) * (a2 - 1.0) + 1.0;
+    a2 / (PI * denominator.powi(2))
+}
+
+fn smith_geometry(cos: f64, roughness: f64) -> f64 {
+    let k = (roughness + 1.0).powi(2) / 8.0;
+    cos / (cos * (1.0 - k) + k)
 }
 
 impl Default for Material {
@@ -79,6 +252,15 @@ impl Default for Material {
             reflective: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            shading: Shading::Phong,
+            roughness: 0.0,
+            metallic: 0.0,
+            roughness_map: None,
+            metallic_map: None,
+            absorption: WHITE,
+            absorption_density: 0.0,
+            dispersion: None,
+            volume: None,
         }
     }
 }
@@ -94,6 +276,15 @@ pub struct MaterialBuilder {
     reflective: Option<f64>,
     transparency: Option<f64>,
     refractive_index: Option<f64>,
+    shading: Option<Shading>,
+    roughness: Option<f64>,
+    metallic: Option<f64>,
+    roughness_map: Option<Pattern>,
+    metallic_map: Option<Pattern>,
+    absorption: Option<Color>,
+    absorption_density: Option<f64>,
+    dispersion: Option<Dispersion>,
+    volume: Option<Volume>,
 }
 
 impl MaterialBuilder {
@@ -133,6 +324,42 @@ impl MaterialBuilder {
         self.refractive_index = Some(refractive_index);
         self
     }
+    pub fn shading(mut self, shading: Shading) -> MaterialBuilder {
+        self.shading = Some(shading);
+        self
+    }
+    pub fn roughness(mut self, roughness: f64) -> MaterialBuilder {
+        self.roughness = Some(roughness);
+        self
+    }
+    pub fn metallic(mut self, metallic: f64) -> MaterialBuilder {
+        self.metallic = Some(metallic);
+        self
+    }
+    pub fn roughness_map(mut self, roughness_map: Pattern) -> MaterialBuilder {
+        self.roughness_map = Some(roughness_map);
+        self
+    }
+    pub fn metallic_map(mut self, metallic_map: Pattern) -> MaterialBuilder {
+        self.metallic_map = Some(metallic_map);
+        self
+    }
+    pub fn absorption(mut self, absorption: Color) -> MaterialBuilder {
+        self.absorption = Some(absorption);
+        self
+    }
+    pub fn absorption_density(mut self, absorption_density: f64) -> MaterialBuilder {
+        self.absorption_density = Some(absorption_density);
+        self
+    }
+    pub fn dispersion(mut self, dispersion: Dispersion) -> MaterialBuilder {
+        self.dispersion = Some(dispersion);
+        self
+    }
+    pub fn volume(mut self, volume: Volume) -> MaterialBuilder {
+        self.volume = Some(volume);
+        self
+    }
     pub fn build(self) -> Material {
         Material {
             color: self.color.unwrap_or(WHITE),
@@ -144,6 +371,15 @@ impl MaterialBuilder {
             reflective: self.reflective.unwrap_or_default(),
             transparency: self.transparency.unwrap_or_default(),
             refractive_index: self.refractive_index.unwrap_or(1.0),
+            shading: self.shading.unwrap_or_default(),
+            roughness: self.roughness.unwrap_or_default(),
+            metallic: self.metallic.unwrap_or_default(),
+            roughness_map: self.roughness_map,
+            metallic_map: self.metallic_map,
+            absorption: self.absorption.unwrap_or(WHITE),
+            absorption_density: self.absorption_density.unwrap_or_default(),
+            dispersion: self.dispersion,
+            volume: self.volume,
         }
     }
 }
@@ -290,3 +526,151 @@ mod refraction_tests {
         assert_eq!(m.refractive_index, 1.0);
     }
 }
+
+#[cfg(test)]
+mod absorption_tests {
+
+    use super::*;
+
+    #[test]
+    fn clear_material_transmits_everything() {
+        let m = Material::default();
+        assert_eq!(m.transmittance(10.0), WHITE);
+    }
+
+    #[test]
+    fn thicker_medium_absorbs_more() {
+        let m = Material::builder()
+            .absorption(Color::new(1.0, 0.5, 0.25))
+            .absorption_density(1.0)
+            .build();
+        assert_eq!(m.transmittance(1.0), Color::new(1.0, 0.5, 0.25));
+        assert_eq!(m.transmittance(2.0), Color::new(1.0, 0.25, 0.0625));
+    }
+}
+
+#[cfg(test)]
+mod dispersion_tests {
+
+    use super::*;
+
+    #[test]
+    fn without_dispersion_index_is_constant() {
+        let m = Material::builder().refractive_index(1.5).build();
+        assert_eq!(m.refractive_index_at(Some(450.0)), 1.5);
+        assert_eq!(m.refractive_index_at(None), 1.5);
+    }
+
+    #[test]
+    fn abbe_number_keeps_index_at_d_line() {
+        let m = Material::builder()
+            .refractive_index(1.5168)
+            .dispersion(Dispersion::Abbe(64.17))
+            .build();
+        assert!((m.refractive_index_at(Some(WAVELENGTH_D)) - 1.5168).abs() < 1e-12);
+        let spread =
+            m.refractive_index_at(Some(WAVELENGTH_F)) - m.refractive_index_at(Some(WAVELENGTH_C));
+        assert!((spread - (1.5168 - 1.0) / 64.17).abs() < 1e-12);
+    }
+
+    #[test]
+    fn blue_bends_more_than_red() {
+        let m = Material::builder()
+            .dispersion(Dispersion::Cauchy(1.5, 0.004))
+            .build();
+        assert!(m.refractive_index_at(Some(450.0)) > m.refractive_index_at(Some(650.0)));
+        assert_eq!(m.refractive_index_at(Some(1000.0)), 1.504);
+    }
+}
+
+#[cfg(test)]
+mod microfacet_tests {
+
+    use super::*;
+
+    fn microfacet(roughness: f64, metallic: f64) -> Material {
+        Material::builder()
+            .shading(Shading::Microfacet)
+            .roughness(roughness)
+            .metallic(metallic)
+            .build()
+    }
+
+    #[test]
+    fn default_shading_is_phong() {
+        let m = Material::default();
+        assert_eq!(m.shading, Shading::Phong);
+        assert_eq!(m.roughness, 0.0);
+        assert_eq!(m.metallic, 0.0);
+    }
+
+    #[test]
+    fn light_behind_surface_leaves_ambient() {
+        let m = microfacet(0.5, 0.0);
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normalv = Vector::new(0.0, 0.0, -1.0);
+        let light = Light::new(Point::new(0.0, 0.0, 10.0), WHITE);
+        let result = m.lighting(
+            &light,
+            &Object::default(),
+            &Point::default(),
+            &eyev,
+            &normalv,
+            false,
+        );
+        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn smoother_surface_has_brighter_highlight() {
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normalv = Vector::new(0.0, 0.0, -1.0);
+        let light = Light::new(Point::new(0.0, 0.0, -10.0), WHITE);
+        let shade = |m: Material| {
+            m.lighting(
+                &light,
+                &Object::default(),
+                &Point::default(),
+                &eyev,
+                &normalv,
+                false,
+            )
+        };
+        let smooth = shade(microfacet(0.2, 0.0));
+        let rough = shade(microfacet(0.8, 0.0));
+        assert!(smooth.rgb.x > rough.rgb.x);
+    }
+
+    #[test]
+    fn metal_tints_its_reflection() {
+        let m = Material {
+            color: Color::new(1.0, 0.5, 0.0),
+            ambient: 0.0,
+            ..microfacet(0.3, 1.0)
+        };
+        let eyev = Vector::new(0.0, 0.0, -1.0);
+        let normalv = Vector::new(0.0, 0.0, -1.0);
+        let light = Light::new(Point::new(0.0, 0.0, -10.0), WHITE);
+        let result = m.lighting(
+            &light,
+            &Object::default(),
+            &Point::default(),
+            &eyev,
+            &normalv,
+            false,
+        );
+        assert!(result.rgb.x > result.rgb.y);
+        assert_eq!(result.rgb.z, 0.0);
+    }
+
+    #[test]
+    fn roughness_map_overrides_roughness() {
+        let m = Material::builder()
+            .roughness(0.9)
+            .roughness_map(Pattern::stripe(Color::new(0.25, 0.25, 0.25), WHITE))
+            .build();
+        let object = Object::default();
+        assert_eq!(m.roughness_at(&object, &Point::new(0.5, 0.0, 0.0)), 0.25);
+        assert_eq!(m.roughness_at(&object, &Point::new(1.5, 0.0, 0.0)), 1.0);
+    }
+}
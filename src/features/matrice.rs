@@ -90,6 +90,26 @@ impl Matrice {
         out
     }
 
+    // Blends two affine transformations through their translations,
+    // rotations and stretches, so an object turning between them keeps its
+    // size on the way. Both must be invertible.
+    pub fn interpolate(&self, other: &Matrice, t: f64) -> Self {
+        let (a, b) = (Affine::of(self), Affine::of(other));
+        let rotation = slerp(a.rotation, b.rotation, t);
+        let translation: [f64; 3] =
+            std::array::from_fn(|i| a.translation[i] + (b.translation[i] - a.translation[i]) * t);
+        let stretch: Mat3 = std::array::from_fn(|i| {
+            std::array::from_fn(|j| a.stretch[i][j] + (b.stretch[i][j] - a.stretch[i][j]) * t)
+        });
+        let upper = mul3(&rotation_matrix(rotation), &stretch);
+        let mut out = Self::identity();
+        for ix in 0..3 {
+            out.data[ix][..3].copy_from_slice(&upper[ix]);
+            out.data[ix][3] = translation[ix];
+        }
+        out
+    }
+
     pub fn minor(&self, row: usize, column: usize) -> f64 {
         self.submatrix(row, column).determinant()
     }
@@ -102,6 +122,157 @@ impl Matrice {
     }
 }
 
+type Mat3 = [[f64; 3]; 3];
+
+// an affine transformation taken apart as translation * rotation * stretch,
+// the rotation as a unit quaternion (w, x, y, z)
+struct Affine {
+    translation: [f64; 3],
+    rotation: [f64; 4],
+    stretch: Mat3,
+}
+
+impl Affine {
+    fn of(m: &Matrice) -> Affine {
+        let upper: Mat3 = std::array::from_fn(|i| std::array::from_fn(|j| m.data[i][j]));
+        // polar decomposition: averaging with the inverse transpose converges
+        // on the nearest rotation
+        let mut rotation = upper;
+        for _ in 0..100 {
+            let inverse = inverse3(&rotation);
+            let next: Mat3 = std::array::from_fn(|i| {
+                std::array::from_fn(|j| (rotation[i][j] + inverse[j][i]) / 2.0)
+            });
+            let change = (0..9).fold(0.0f64, |max, k| {
+                max.max((next[k / 3][k % 3] - rotation[k / 3][k % 3]).abs())
+            });
+            rotation = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+        // a mirror is kept in the stretch, so the rotation is a proper one
+        if det3(&rotation) < 0.0 {
+            rotation = rotation.map(|row| row.map(|x| -x));
+        }
+        Affine {
+            translation: [m.data[0][3], m.data[1][3], m.data[2][3]],
+            rotation: quaternion(&rotation),
+            stretch: mul3(&transpose3(&rotation), &upper),
+        }
+    }
+}
+
+fn mul3(a: &Mat3, b: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn transpose3(a: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| a[j][i]))
+}
+
+fn det3(a: &Mat3) -> f64 {
+    a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+        - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+        + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0])
+}
+
+fn inverse3(a: &Mat3) -> Mat3 {
+    let det = det3(a);
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            let (r1, r2) = ((j + 1) % 3, (j + 2) % 3);
+            let (c1, c2) = ((i + 1) % 3, (i + 2) % 3);
+            (a[r1][c1] * a[r2][c2] - a[r1][c2] * a[r2][c1]) / det
+        })
+    })
+}
+
+fn quaternion(r: &Mat3) -> [f64; 4] {
+    let trace = r[0][0] + r[1][1] + r[2][2];
+    let q = if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        [
+            s / 4.0,
+            (r[2][1] - r[1][2]) / s,
+            (r[0][2] - r[2][0]) / s,
+            (r[1][0] - r[0][1]) / s,
+        ]
+    } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+        let s = (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt() * 2.0;
+        [
+            (r[2][1] - r[1][2]) / s,
+            s / 4.0,
+            (r[0][1] + r[1][0]) / s,
+            (r[0][2] + r[2][0]) / s,
+        ]
+    } else if r[1][1] > r[2][2] {
+        let s = (1.0 + r[1][1] - r[0][0] - r[2][2]).sqrt() * 2.0;
+        [
+            (r[0][2] - r[2][0]) / s,
+            (r[0][1] + r[1][0]) / s,
+            s / 4.0,
+            (r[1][2] + r[2][1]) / s,
+        ]
+    } else {
+        let s = (1.0 + r[2][2] - r[0][0] - r[1][1]).sqrt() * 2.0;
+        [
+            (r[1][0] - r[0][1]) / s,
+            (r[0][2] + r[2][0]) / s,
+            (r[1][2] + r[2][1]) / s,
+            s / 4.0,
+        ]
+    };
+    normalized(q)
+}
+
+fn normalized(q: [f64; 4]) -> [f64; 4] {
+    let length = q.iter().map(|x| x * x).sum::<f64>().sqrt();
+    q.map(|x| x / length)
+}
+
+// along the shorter arc between the two rotations
+fn slerp(a: [f64; 4], b: [f64; 4], t: f64) -> [f64; 4] {
+    let mut dot: f64 = (0..4).map(|i| a[i] * b[i]).sum();
+    let b = match dot < 0.0 {
+        true => {
+            dot = -dot;
+            b.map(|x| -x)
+        }
+        false => b,
+    };
+    if dot > 1.0 - 1e-9 {
+        return normalized(std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t));
+    }
+    let angle = dot.acos();
+    let (wa, wb) = (
+        ((1.0 - t) * angle).sin() / angle.sin(),
+        (t * angle).sin() / angle.sin(),
+    );
+    std::array::from_fn(|i| a[i] * wa + b[i] * wb)
+}
+
+fn rotation_matrix(q: [f64; 4]) -> Mat3 {
+    let [w, x, y, z] = q;
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+        ],
+        [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+        ],
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ]
+}
+
 impl Default for Matrice {
     fn default() -> Self {
         Self::identity()
@@ -0,0 +1,85 @@
+use super::{color::Color, consts::EPSILON, ray::Ray};
+
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+pub struct Fog {
+    pub color: Color,
+    pub density: f64,
+    pub height_falloff: f64,
+}
+
+impl Fog {
+    pub fn new(color: Color, density: f64, height_falloff: f64) -> Self {
+        Fog {
+            color,
+            density,
+            height_falloff,
+        }
+    }
+
+    // Fraction of light surviving along `ray` up to `t`. The density decays
+    // exponentially with height, so the optical depth has a closed form.
+    pub fn transmittance(&self, ray: &Ray, t: f64) -> f64 {
+        if self.density <= 0.0 {
+            return 1.0;
+        }
+        let length = ray.direction.magnitude();
+        let base = self.density * (-self.height_falloff * ray.origin.y()).exp();
+        let slope = self.height_falloff * ray.direction.y();
+        let depth = if slope.abs() < EPSILON {
+            base * t * length
+        } else {
+            base * (1.0 - (-slope * t).exp()) / slope * length
+        };
+        (-depth).exp()
+    }
+
+    pub fn apply(&self, color: Color, ray: &Ray, t: f64) -> Color {
+        let transmittance = self.transmittance(ray, t);
+        color * transmittance + self.color * (1.0 - transmittance)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
+pub struct Volume {
+    pub color: Color,
+    pub density: f64,
+}
+
+impl Volume {
+    pub fn new(color: Color, density: f64) -> Self {
+        Volume { color, density }
+    }
+    pub fn transmittance(&self, distance: f64) -> f64 {
+        (-self.density * distance).exp()
+    }
+}
+
+#[cfg(test)]
+mod fog_tests {
+    use crate::features::{consts::WHITE, point::Point, vector::Vector, BLACK};
+
+    use super::*;
+
+    #[test]
+    fn uniform_fog_follows_beer_lambert() {
+        let fog = Fog::new(WHITE, 0.5, 0.0);
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 2.0));
+        assert_eq!(fog.transmittance(&r, 1.0), (-1.0_f64).exp());
+    }
+
+    #[test]
+    fn fog_thins_out_with_height() {
+        let fog = Fog::new(WHITE, 0.5, 0.3);
+        let low = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        let high = Ray::new(Point::new(0.0, 10.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(fog.transmittance(&high, 5.0) > fog.transmittance(&low, 5.0));
+    }
+
+    #[test]
+    fn ray_escaping_upwards_keeps_some_light() {
+        let fog = Fog::new(WHITE, 0.5, 0.3);
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let color = fog.apply(BLACK, &r, f64::INFINITY);
+        assert!(color.rgb.x > 0.0 && color.rgb.x < 1.0);
+    }
+}
@@ -1,5 +1,8 @@
+use std::borrow::Cow;
+
 use super::{
-    material::Material, matrice::Matrice, point::Point, ray::Ray, shape::Shape, vector::Vector,
+    material::Material, matrice::Matrice, point::Point, ray::Ray, shape::Shape, stats,
+    vector::Vector,
 };
 
 #[derive(Default, Debug, PartialEq, PartialOrd)]
@@ -9,6 +12,7 @@ pub struct Object {
     transformation: Matrice,
     transformation_inverse: Matrice,
     transformation_inverse_transpose: Matrice,
+    motion: Option<Matrice>,
 }
 
 impl Object {
@@ -43,19 +47,37 @@ impl Object {
     }
 
     pub fn intersect(&self, ray: &Ray) -> Option<Vec<f64>> {
-        let transformed_ray = ray.transform(&self.transformation_inverse);
+        stats::count_intersection_test(&self.shape);
+        let transformed_ray = ray.transform(&self.transformation_inverse_at(ray.time));
         self.shape.intersect(&transformed_ray)
     }
-    pub fn normal_at(&self, world_point: &Point) -> Vector {
-        let object_point = self.world_to_object(world_point);
+    pub fn normal_at(&self, world_point: &Point, time: f64) -> Vector {
+        let inverse = self.transformation_inverse_at(time);
+        let object_point = &*inverse * world_point;
         let local_normal = self.shape.normal_at(&object_point);
-        self.normal_to_world(&local_normal)
+        if self.is_moving() {
+            (&inverse.transpose() * &local_normal).normalize()
+        } else {
+            (&self.transformation_inverse_transpose * &local_normal).normalize()
+        }
+    }
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+    // time 0.0 is the object's transformation and 1.0 its motion end
+    pub fn transformation_inverse_at(&self, time: f64) -> Cow<'_, Matrice> {
+        match &self.motion {
+            Some(end) if time != 0.0 => {
+                Cow::Owned(self.transformation.interpolate(end, time).inverse())
+            }
+            _ => Cow::Borrowed(&self.transformation_inverse),
+        }
     }
-    fn world_to_object(&self, world_point: &Point) -> Point {
-        &self.transformation_inverse * world_point
+    pub fn motion(&self) -> Option<&Matrice> {
+        self.motion.as_ref()
     }
-    fn normal_to_world(&self, local_normal: &Vector) -> Vector {
-        (&self.transformation_inverse_transpose * local_normal).normalize()
+    pub fn set_motion(&mut self, end: Matrice) {
+        self.motion = Some(end);
     }
     pub fn transformation_inverse(&self) -> &Matrice {
         &self.transformation_inverse
@@ -74,6 +96,7 @@ pub struct ObjectBuilder {
     material: Option<Material>,
     shape: Option<Shape>,
     transformation: Option<Matrice>,
+    motion: Option<Matrice>,
 }
 
 impl ObjectBuilder {
@@ -119,12 +142,18 @@ impl ObjectBuilder {
         self.transformation = Some(transformation);
         self
     }
+    pub fn motion(mut self, end: Matrice) -> ObjectBuilder {
+        self.motion = Some(end);
+        self
+    }
     pub fn build(self) -> Object {
-        Object::new(
+        let mut object = Object::new(
             self.material.unwrap_or_default(),
             self.shape.unwrap_or_default(),
             self.transformation.unwrap_or_default(),
-        )
+        );
+        object.motion = self.motion;
+        object
     }
 }
 
@@ -142,3 +171,63 @@ mod object_builder_tests {
         assert_eq!(object.shape, Shape::Sphere)
     }
 }
+
+#[cfg(test)]
+mod motion_tests {
+    use std::f64::consts::PI;
+
+    use crate::features::transformations::{rotation_x, rotation_y, scaling, translation};
+
+    use super::*;
+
+    #[test]
+    fn static_object_ignores_time() {
+        let object = Object::sphere_builder().build();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)).with_time(0.7);
+        assert_eq!(object.intersect(&r), Some(vec![4.0, 6.0]));
+    }
+
+    #[test]
+    fn moving_object_is_intersected_where_it_is_at_ray_time() {
+        let object = Object::sphere_builder()
+            .motion(translation(0.0, 0.0, 2.0))
+            .build();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(object.intersect(&r), Some(vec![4.0, 6.0]));
+        assert_eq!(object.intersect(&r.with_time(0.5)), Some(vec![5.0, 7.0]));
+        assert_eq!(object.intersect(&r.with_time(1.0)), Some(vec![6.0, 8.0]));
+    }
+
+    fn close(a: &Matrice, b: &Matrice) -> bool {
+        a.data
+            .iter()
+            .flatten()
+            .zip(b.data.iter().flatten())
+            .all(|(a, b)| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn turning_object_keeps_its_size() {
+        let object = Object::sphere_builder()
+            .motion(rotation_y(PI / 2.0))
+            .build();
+        let halfway = object.transformation_inverse_at(0.5);
+        assert!(close(&halfway, &rotation_y(PI / 4.0).inverse()));
+        let start = translation(1.0, 2.0, 3.0) * scaling(2.0, 1.0, 1.0);
+        let end = translation(-1.0, 0.0, 3.0) * rotation_x(PI) * scaling(1.0, 3.0, 1.0);
+        assert!(close(&start.interpolate(&end, 0.0), &start));
+        assert!(close(&start.interpolate(&end, 1.0), &end));
+        // half a turn does not collapse the object halfway through
+        let halfway = start.interpolate(&end, 0.5);
+        assert!((halfway.determinant() - 1.5 * 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn moving_object_normal_follows_motion() {
+        let object = Object::sphere_builder()
+            .motion(translation(2.0, 0.0, 0.0))
+            .build();
+        let n = object.normal_at(&Point::new(2.0, 1.0, 0.0), 1.0);
+        assert_eq!(n, Vector::new(0.0, 1.0, 0.0));
+    }
+}
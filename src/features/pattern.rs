@@ -60,6 +60,12 @@ impl Pattern {
             ..Default::default()
         }
     }
+    pub fn pattern_type(&self) -> &PatternType {
+        &self.pattern_type
+    }
+    pub fn transformation(&self) -> &Matrice {
+        &self.transformation
+    }
     pub fn set_transformation(&mut self, transformation: Matrice) {
         self.transformation = transformation;
         self.transformation_inverse = self.transformation.inverse();
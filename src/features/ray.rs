@@ -1,20 +1,40 @@
 use super::{matrice::Matrice, point::Point, vector::Vector};
 
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
+    pub wavelength: Option<f64>,
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            wavelength: None,
+            time: 0.0,
+        }
+    }
+    pub fn with_wavelength(mut self, wavelength: Option<f64>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
     }
     pub fn position(&self, t: f64) -> Point {
         self.origin + self.direction * t
     }
 
     pub fn transform(&self, m: &Matrice) -> Self {
-        Ray::new(m * &self.origin, m * &self.direction)
+        Ray {
+            origin: m * &self.origin,
+            direction: m * &self.direction,
+            ..*self
+        }
     }
 }
 
@@ -41,4 +61,12 @@ mod ray_transformation_tests {
         assert_eq!(r2.origin, Point::new(2.0, 6.0, 12.0));
         assert_eq!(r2.direction, Vector::new(0.0, 3.0, 0.0));
     }
+
+    #[test]
+    fn transforming_keeps_wavelength() {
+        let r = Ray::new(Point::new(1.0, 2.0, 3.0), Vector::new(0.0, 1.0, 0.0))
+            .with_wavelength(Some(450.0));
+        let r2 = r.transform(&translation(3.0, 4.0, 5.0));
+        assert_eq!(r2.wavelength, Some(450.0));
+    }
 }
@@ -0,0 +1,43 @@
+use rand::Rng;
+
+use super::vector::Vector;
+
+pub fn random_in_unit_sphere() -> Vector {
+    let mut rng = rand::thread_rng();
+    loop {
+        let v = Vector::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        if v.dot_product(&v) < 1.0 {
+            return v;
+        }
+    }
+}
+
+pub fn random_in_unit_disk() -> (f64, f64) {
+    let mut rng = rand::thread_rng();
+    loop {
+        let (x, y): (f64, f64) = (rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+        if x * x + y * y < 1.0 {
+            return (x, y);
+        }
+    }
+}
+
+pub fn random_unit_vector() -> Vector {
+    random_in_unit_sphere().normalize()
+}
+
+#[cfg(test)]
+mod sampling_tests {
+    use super::*;
+
+    #[test]
+    fn samples_lie_inside_unit_sphere() {
+        for _ in 0..100 {
+            assert!(random_in_unit_sphere().magnitude() < 1.0);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::{cell::RefCell, fmt, time::Duration};
+
+use serde::{Serialize, Serializer};
+
+use super::{shape::Shape, tile::Tile};
+
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RayKind {
+    Primary,
+    Shadow,
+    Reflection,
+    Refraction,
+}
+
+// per-thread counters, bumped while tracing and drained by the renderer
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Counters {
+    rays: [u64; 4],
+    intersection_tests: [u64; 6],
+    remaining: [u64; MAX_DEPTH],
+}
+
+thread_local! {
+    static COUNTERS: RefCell<Counters> = RefCell::new(Counters::default());
+}
+
+pub(crate) fn count_ray(kind: RayKind) {
+    COUNTERS.with(|c| c.borrow_mut().rays[kind as usize] += 1);
+}
+
+pub(crate) fn count_intersection_test(shape: &Shape) {
+    let variant = match shape {
+        Shape::Plane => 0,
+        Shape::Sphere => 1,
+        Shape::Cube => 2,
+        Shape::Cylinder(..) => 3,
+        Shape::Cone(..) => 4,
+        Shape::Group(_) => 5,
+    };
+    COUNTERS.with(|c| c.borrow_mut().intersection_tests[variant] += 1);
+}
+
+// records a ray being traced with `remaining` bounces left
+pub(crate) fn count_remaining(remaining: usize) {
+    COUNTERS.with(|c| c.borrow_mut().remaining[remaining.min(MAX_DEPTH - 1)] += 1);
+}
+
+pub(crate) fn take() -> Counters {
+    COUNTERS.with(|c| c.take())
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct RayCounts {
+    pub primary: u64,
+    pub shadow: u64,
+    pub reflection: u64,
+    pub refraction: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct IntersectionTests {
+    pub plane: u64,
+    pub sphere: u64,
+    pub cube: u64,
+    pub cylinder: u64,
+    pub cone: u64,
+    pub group: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TileTiming {
+    pub tile: Tile,
+    #[serde(serialize_with = "seconds")]
+    pub time: Duration,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct RenderStats {
+    pub rays: RayCounts,
+    pub intersection_tests: IntersectionTests,
+    // number of rays traced at each recursion depth, primary rays at 0
+    pub depth_histogram: Vec<u64>,
+    pub tiles: Vec<TileTiming>,
+    #[serde(serialize_with = "seconds")]
+    pub total_time: Duration,
+}
+
+impl RenderStats {
+    pub(crate) fn add(&mut self, counters: &Counters, max_depth: usize) {
+        let [primary, shadow, reflection, refraction] = counters.rays;
+        self.rays.primary += primary;
+        self.rays.shadow += shadow;
+        self.rays.reflection += reflection;
+        self.rays.refraction += refraction;
+        let [plane, sphere, cube, cylinder, cone, group] = counters.intersection_tests;
+        let tests = &mut self.intersection_tests;
+        tests.plane += plane;
+        tests.sphere += sphere;
+        tests.cube += cube;
+        tests.cylinder += cylinder;
+        tests.cone += cone;
+        tests.group += group;
+        let max_depth = max_depth.min(MAX_DEPTH - 1);
+        self.depth_histogram.resize(max_depth + 1, 0);
+        for (depth, count) in self.depth_histogram.iter_mut().enumerate() {
+            *count += counters.remaining[max_depth - depth];
+        }
+    }
+
+    pub fn total_rays(&self) -> u64 {
+        self.rays.primary + self.rays.shadow + self.rays.reflection + self.rays.refraction
+    }
+}
+
+fn seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "total time: {:.3}s", self.total_time.as_secs_f64())?;
+        writeln!(f, "rays: {}", self.total_rays())?;
+        let rays = &self.rays;
+        writeln!(f, "  primary: {}", rays.primary)?;
+        writeln!(f, "  shadow: {}", rays.shadow)?;
+        writeln!(f, "  reflection: {}", rays.reflection)?;
+        writeln!(f, "  refraction: {}", rays.refraction)?;
+        let tests = &self.intersection_tests;
+        writeln!(f, "intersection tests:")?;
+        writeln!(f, "  plane: {}", tests.plane)?;
+        writeln!(f, "  sphere: {}", tests.sphere)?;
+        writeln!(f, "  cube: {}", tests.cube)?;
+        writeln!(f, "  cylinder: {}", tests.cylinder)?;
+        writeln!(f, "  cone: {}", tests.cone)?;
+        writeln!(f, "  group: {}", tests.group)?;
+        writeln!(f, "depth histogram:")?;
+        for (depth, count) in self.depth_histogram.iter().enumerate() {
+            writeln!(f, "  {}: {}", depth, count)?;
+        }
+        if let Some(slowest) = self.tiles.iter().max_by_key(|t| t.time) {
+            writeln!(
+                f,
+                "tiles: {}, slowest at ({}, {}) took {:.3}s",
+                self.tiles.len(),
+                slowest.tile.x,
+                slowest.tile.y,
+                slowest.time.as_secs_f64()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    #[test]
+    fn counters_are_drained_by_take() {
+        take();
+        count_ray(RayKind::Shadow);
+        count_intersection_test(&Shape::Cube);
+        let counters = take();
+        assert_eq!(counters.rays, [0, 1, 0, 0]);
+        assert_eq!(counters.intersection_tests, [0, 0, 1, 0, 0, 0]);
+        assert_eq!(take(), Counters::default());
+    }
+
+    #[test]
+    fn depth_counts_down_from_remaining() {
+        take();
+        count_remaining(4);
+        count_remaining(4);
+        count_remaining(3);
+        let mut stats = RenderStats::default();
+        stats.add(&take(), 4);
+        assert_eq!(stats.depth_histogram, vec![2, 1, 0, 0, 0]);
+    }
+}
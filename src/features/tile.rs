@@ -0,0 +1,390 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use super::{canvas::Canvas, color::Color};
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"RTCK";
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum TileOrder {
+    #[default]
+    Scanline,
+    // outward from the centre of the image
+    Spiral,
+    // along a Hilbert curve, keeping consecutive tiles adjacent
+    Hilbert,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileOptions {
+    pub tile_size: usize,
+    pub order: TileOrder,
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    pub progress: bool,
+    // identifies the scene, so that a checkpoint left by another is not resumed
+    pub scene_hash: u64,
+    // collect auxiliary passes alongside the image
+    pub aovs: bool,
+}
+
+impl Default for TileOptions {
+    fn default() -> Self {
+        TileOptions {
+            tile_size: 32,
+            order: TileOrder::default(),
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(30),
+            progress: true,
+            scene_hash: 0,
+            aovs: false,
+        }
+    }
+}
+
+impl Tile {
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let tile = |(column, row): (usize, usize)| Tile {
+        x: column * size,
+        y: row * size,
+        width: size.min(width - column * size),
+        height: size.min(height - row * size),
+    };
+    let mut cells: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let centre_x = (columns as f64 - 1.0) / 2.0;
+            let centre_y = (rows as f64 - 1.0) / 2.0;
+            let key = |&(column, row): &(usize, usize)| {
+                let (dx, dy) = (column as f64 - centre_x, row as f64 - centre_y);
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let n = columns.max(rows).next_power_of_two();
+            cells = (0..n * n)
+                .map(|d| hilbert_cell(n, d))
+                .filter(|&(column, row)| column < columns && row < rows)
+                .collect();
+        }
+    }
+    cells.into_iter().map(tile).collect()
+}
+
+// maps a distance along the curve to a cell of an n x n grid
+fn hilbert_cell(n: usize, d: usize) -> (usize, usize) {
+    let (mut x, mut y, mut t) = (0, 0, d);
+    let mut s = 1;
+    while s < n {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+// append-only record of finished tiles; a render killed mid-write loses at
+// most the tile that was being written
+pub struct Checkpoint {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+// what a checkpoint was rendered for, written at its start
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Header {
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    scene_hash: u64,
+}
+
+impl Checkpoint {
+    // opens the checkpoint at `path`, copying any tiles it already holds into
+    // `canvas`; a checkpoint for a different image size, tile size or scene
+    // is discarded
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        canvas: &mut Canvas,
+        tile_size: usize,
+        scene_hash: u64,
+    ) -> io::Result<(Self, Vec<Tile>)> {
+        let path = path.as_ref().to_path_buf();
+        let header = Header {
+            width: canvas.width,
+            height: canvas.height,
+            tile_size,
+            scene_hash,
+        };
+        let done = match File::open(&path) {
+            Ok(file) => read_tiles(BufReader::new(file), header, canvas).unwrap_or_default(),
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        // what was recovered is rewritten, dropping a truncated tail, into a
+        // new file that only replaces the old one once it is complete
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let writer = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&temporary)?,
+        );
+        let mut checkpoint = Checkpoint { path, writer };
+        checkpoint.write_header(header)?;
+        for tile in &done {
+            checkpoint.push(*tile, canvas)?;
+        }
+        checkpoint.flush()?;
+        fs::rename(&temporary, &checkpoint.path)?;
+        Ok((checkpoint, done))
+    }
+
+    fn write_header(&mut self, header: Header) -> io::Result<()> {
+        self.writer.write_all(CHECKPOINT_MAGIC)?;
+        write_u64(&mut self.writer, header.width)?;
+        write_u64(&mut self.writer, header.height)?;
+        write_u64(&mut self.writer, header.tile_size)?;
+        self.writer.write_all(&header.scene_hash.to_le_bytes())
+    }
+
+    pub fn push(&mut self, tile: Tile, canvas: &Canvas) -> io::Result<()> {
+        for value in [tile.x, tile.y, tile.width, tile.height] {
+            write_u64(&mut self.writer, value)?;
+        }
+        for (x, y) in tile.pixels() {
+            let rgb = canvas.canvas[y][x].rgb;
+            for channel in [rgb.x, rgb.y, rgb.z] {
+                self.writer.write_all(&channel.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+
+    // the render finished, so there is nothing left to resume
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.writer);
+        fs::remove_file(self.path)
+    }
+}
+
+fn read_tiles<R: Read>(
+    mut reader: R,
+    expected: Header,
+    canvas: &mut Canvas,
+) -> io::Result<Vec<Tile>> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    let header = Header {
+        width: read_u64(&mut reader)? as usize,
+        height: read_u64(&mut reader)? as usize,
+        tile_size: read_u64(&mut reader)? as usize,
+        scene_hash: read_u64(&mut reader)?,
+    };
+    if &magic != CHECKPOINT_MAGIC || header != expected {
+        return Ok(Vec::new());
+    }
+    let mut done = Vec::new();
+    while let Ok(tile) = read_tile(&mut reader, canvas) {
+        done.push(tile);
+    }
+    Ok(done)
+}
+
+fn read_tile<R: Read>(reader: &mut R, canvas: &mut Canvas) -> io::Result<Tile> {
+    let tile = Tile {
+        x: read_u64(reader)? as usize,
+        y: read_u64(reader)? as usize,
+        width: read_u64(reader)? as usize,
+        height: read_u64(reader)? as usize,
+    };
+    if tile.x + tile.width > canvas.width || tile.y + tile.height > canvas.height {
+        return Err(ErrorKind::InvalidData.into());
+    }
+    let mut pixels = Vec::with_capacity(tile.width * tile.height);
+    for _ in 0..tile.width * tile.height {
+        pixels.push(Color::new(
+            read_f64(reader)?,
+            read_f64(reader)?,
+            read_f64(reader)?,
+        ));
+    }
+    for ((x, y), color) in tile.pixels().zip(pixels) {
+        canvas.canvas[y][x] = color;
+    }
+    Ok(tile)
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: usize) -> io::Result<()> {
+    writer.write_all(&(value as u64).to_le_bytes())
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tile_tests {
+    use crate::features::{BLACK, RED};
+
+    use super::*;
+
+    fn covered(tiles: &[Tile], width: usize, height: usize) -> bool {
+        let mut seen = vec![vec![0; width]; height];
+        for tile in tiles {
+            for (x, y) in tile.pixels() {
+                seen[y][x] += 1;
+            }
+        }
+        seen.iter().flatten().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn every_order_covers_the_image_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let t = tiles(70, 45, 16, order);
+            assert_eq!(t.len(), 5 * 3);
+            assert!(covered(&t, 70, 45));
+        }
+    }
+
+    #[test]
+    fn edge_tiles_are_clipped() {
+        let t = tiles(20, 10, 16, TileOrder::Scanline);
+        assert_eq!(
+            t[1],
+            Tile {
+                x: 16,
+                y: 0,
+                width: 4,
+                height: 10
+            }
+        );
+    }
+
+    #[test]
+    fn spiral_starts_at_the_centre() {
+        let t = tiles(48, 48, 16, TileOrder::Spiral);
+        assert_eq!((t[0].x, t[0].y), (16, 16));
+    }
+
+    #[test]
+    fn hilbert_steps_between_neighbours() {
+        let t = tiles(64, 64, 16, TileOrder::Hilbert);
+        for pair in t.windows(2) {
+            let dx = pair[0].x.abs_diff(pair[1].x);
+            let dy = pair[0].y.abs_diff(pair[1].y);
+            assert_eq!(dx + dy, 16);
+        }
+    }
+
+    #[test]
+    fn checkpoint_restores_finished_tiles() {
+        let path = std::env::temp_dir().join("rt_checkpoint_restores_finished_tiles");
+        let _ = fs::remove_file(&path);
+        let tile = Tile {
+            x: 2,
+            y: 0,
+            width: 2,
+            height: 2,
+        };
+        let mut canvas = Canvas::new(4, 4);
+        let (mut checkpoint, done) = Checkpoint::open(&path, &mut canvas, 2, 7).unwrap();
+        assert!(done.is_empty());
+        for (x, y) in tile.pixels() {
+            canvas.canvas[y][x] = RED;
+        }
+        checkpoint.push(tile, &canvas).unwrap();
+        checkpoint.flush().unwrap();
+        drop(checkpoint);
+
+        let mut resumed = Canvas::new(4, 4);
+        let (checkpoint, done) = Checkpoint::open(&path, &mut resumed, 2, 7).unwrap();
+        assert_eq!(done, vec![tile]);
+        assert!(!path.with_extension("tmp").exists());
+        assert_eq!(resumed.canvas[1][3], RED);
+        assert_eq!(resumed.canvas[1][1], BLACK);
+        checkpoint.finish().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn checkpoint_for_another_size_or_scene_is_ignored() {
+        let path = std::env::temp_dir().join("rt_checkpoint_for_another_size_is_ignored");
+        // a checkpoint holding one tile, reopened with other settings
+        let reopened = |width: usize, tile_size: usize, scene_hash: u64| {
+            let mut canvas = Canvas::new(4, 4);
+            let (mut checkpoint, _) = Checkpoint::open(&path, &mut canvas, 1, 7).unwrap();
+            let tile = Tile {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            };
+            checkpoint.push(tile, &canvas).unwrap();
+            checkpoint.flush().unwrap();
+            drop(checkpoint);
+            let mut other = Canvas::new(width, width);
+            let (checkpoint, done) =
+                Checkpoint::open(&path, &mut other, tile_size, scene_hash).unwrap();
+            checkpoint.finish().unwrap();
+            done.len()
+        };
+        assert_eq!(reopened(4, 1, 7), 1);
+        assert_eq!(reopened(8, 1, 7), 0);
+        assert_eq!(reopened(4, 2, 7), 0);
+        assert_eq!(reopened(4, 1, 8), 0);
+    }
+}
@@ -53,6 +53,49 @@ pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Matrice
     matrice
 }
 
+// rotation by `rad` about `axis`, turning the same way as rotation_x and
+// friends do about their axes
+pub fn rotation(axis: Vector, rad: f64) -> Matrice {
+    let k = axis.normalize();
+    let (x, y, z) = (k.x(), k.y(), k.z());
+    let (sin, cos) = rad.sin_cos();
+    let t = 1.0 - cos;
+    let mut matrice = Matrice::identity();
+    matrice.data[0][..3].copy_from_slice(&[
+        t * x * x + cos,
+        t * x * y - sin * z,
+        t * x * z + sin * y,
+    ]);
+    matrice.data[1][..3].copy_from_slice(&[
+        t * x * y + sin * z,
+        t * y * y + cos,
+        t * y * z - sin * x,
+    ]);
+    matrice.data[2][..3].copy_from_slice(&[
+        t * x * z - sin * y,
+        t * y * z + sin * x,
+        t * z * z + cos,
+    ]);
+    matrice
+}
+
+// places an object at `from` facing `to`: like a camera, its -z axis points
+// at the target and +y leans towards `up`
+pub fn look_at(from: Point, to: Point, up: Vector) -> Matrice {
+    let forward = (to - from).normalize();
+    let left = forward.cross_product(&up).normalize();
+    let true_up = left.cross_product(&forward);
+    let mut matrice = translation(from.x(), from.y(), from.z());
+    for (row, axes) in matrice.data.iter_mut().zip([
+        [left.x(), true_up.x(), -forward.x()],
+        [left.y(), true_up.y(), -forward.y()],
+        [left.z(), true_up.z(), -forward.z()],
+    ]) {
+        row[..3].copy_from_slice(&axes);
+    }
+    matrice
+}
+
 pub fn view_transformation(from: Point, to: Point, up: Vector) -> Matrice {
     let forward = (to - from).normalize();
     let left = forward.cross_product(&up.normalize());
@@ -119,3 +162,44 @@ mod view_transformation_tests {
         println!("{:?}", t);
     }
 }
+
+#[cfg(test)]
+mod rotation_tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn assert_close(a: &Matrice, b: &Matrice) {
+        for (a, b) in a.data.iter().flatten().zip(b.data.iter().flatten()) {
+            assert!((a - b).abs() < 1e-9, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn rotation_about_an_axis_matches_the_axis_rotations() {
+        assert_close(&rotation(Vector::new(2.0, 0.0, 0.0), 0.7), &rotation_x(0.7));
+        assert_close(
+            &rotation(Vector::new(0.0, 1.0, 0.0), -1.2),
+            &rotation_y(-1.2),
+        );
+        assert_close(
+            &rotation(Vector::new(0.0, 0.0, 1.0), PI / 3.0),
+            &rotation_z(PI / 3.0),
+        );
+    }
+
+    #[test]
+    fn look_at_points_minus_z_at_the_target() {
+        let m = look_at(
+            Point::new(1.0, 2.0, 3.0),
+            Point::new(1.0, 2.0, 8.0),
+            Vector::new(0.0, 3.0, 0.0),
+        );
+        assert_eq!(&m * &Point::new(0.0, 0.0, 0.0), Point::new(1.0, 2.0, 3.0));
+        assert_eq!(
+            &m * &Vector::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(&m * &Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+    }
+}
@@ -1,18 +1,39 @@
+use rand::Rng;
+
 use super::{
+    aov::AovSample,
+    background::Background,
     color::Color,
     computation::Computation,
     consts::BLACK,
     intersection::{hit, sort_intersections, Intersection},
     light::Light,
     material::Material,
+    media::{Fog, Volume},
     object::Object,
     point::Point,
     ray::Ray,
+    sampling::{random_in_unit_sphere, random_unit_vector},
+    stats::{self, RayKind},
     transformations::scaling,
+    vector::Vector,
 };
+
+// bounces left for a ray leaving the camera
+pub(crate) const REMAINING: usize = 4;
+const GLOSSY_SAMPLES: usize = 8;
+const VOLUME_STEPS: usize = 16;
+const IBL_SAMPLES: usize = 16;
+// wavelengths in nanometres used for the red, green and blue channels of
+// dispersive refraction
+const RGB_WAVELENGTHS: [f64; 3] = [650.0, 550.0, 450.0];
+
 pub struct World {
     pub light: Light,
     pub objects: Vec<Object>,
+    pub fog: Option<Fog>,
+    pub background: Background,
+    pub image_based_lighting: bool,
 }
 
 impl World {
@@ -20,6 +41,9 @@ impl World {
         World {
             light,
             objects: vec![],
+            fog: None,
+            background: Background::default(),
+            image_based_lighting: false,
         }
     }
     pub fn add_shapes(&mut self, objects: Vec<Object>) {
@@ -51,8 +75,13 @@ impl World {
             &comps.point,
             &comps.eyev,
             &comps.normalv,
-            self.is_shadowed(&comps.over_point),
+            self.is_shadowed_at(&comps.over_point, comps.time),
         );
+        let surface = if self.image_based_lighting {
+            surface + self.environment_light(comps)
+        } else {
+            surface
+        };
         if comps.object.material.reflective > 0.0 && comps.object.material.transparency > 0.0 {
             let reflectance = comps.shlick();
             return surface
@@ -62,21 +91,142 @@ impl World {
         surface + self.reflected_color(comps, remaining) + self.refracted_color(comps, remaining)
     }
 
+    // Diffuse light from the background, estimated with cosine-weighted
+    // directions around the normal that are not blocked by other objects.
+    fn environment_light(&self, comps: &Computation) -> Color {
+        let mut irradiance = BLACK;
+        for _ in 0..IBL_SAMPLES {
+            let direction = comps.normalv + random_unit_vector();
+            let ray = comps.spawn_ray(comps.over_point, direction);
+            stats::count_ray(RayKind::Shadow);
+            let blocked = self.intersect(&ray).and_then(hit).is_some();
+            if !blocked {
+                irradiance = irradiance + self.background.at(&direction);
+            }
+        }
+        let material = &comps.object.material;
+        material.color_at(comps.object, &comps.point)
+            * irradiance
+            * (material.diffuse / IBL_SAMPLES as f64)
+    }
+
     pub fn color_at(&self, ray: &Ray, remaining: usize) -> Color {
-        if let Some(ixs) = self.intersect(ray) {
+        self.color_of(ray, self.intersect(ray), remaining)
+    }
+    // the color seen along a primary ray and its auxiliary values, both taken
+    // from the same intersections
+    pub fn sample_at(&self, ray: &Ray, remaining: usize) -> (Color, Option<AovSample>) {
+        let ixs = self.intersect(ray);
+        let aov = ixs.as_ref().and_then(|ixs| self.aov_of(ray, ixs));
+        (self.color_of(ray, ixs, remaining), aov)
+    }
+    fn color_of(&self, ray: &Ray, ixs: Option<Vec<Intersection>>, remaining: usize) -> Color {
+        stats::count_remaining(remaining);
+        if let Some(ixs) = ixs {
             if let Some(hit) = hit(ixs.clone()) {
                 let comps = Computation::new(ray, &hit, &ixs);
-                return self.shade_hit(&comps, remaining);
+                let mut color = if comps.object.material.volume.is_some() {
+                    // volume boundaries are invisible, the ray carries on through
+                    // them at the cost of a bounce so overlapping volumes end
+                    if remaining == 0 {
+                        return BLACK;
+                    }
+                    self.color_at(
+                        &comps.spawn_ray(comps.under_point, ray.direction),
+                        remaining - 1,
+                    )
+                } else {
+                    self.shade_hit(&comps, remaining)
+                };
+                color = color * comps.transmittance();
+                if let Some(medium) = comps.medium.filter(|m| m.material.volume.is_some()) {
+                    color = self.scattered_color(medium, ray, comps.t, color);
+                }
+                return self.fogged(color, ray, comps.t);
             }
         }
-        BLACK
+        self.fogged(self.background.at(&ray.direction), ray, f64::INFINITY)
+    }
+    // auxiliary values at the first surface `ray` hits, looking through volumes
+    pub fn aov_at(&self, ray: &Ray) -> Option<AovSample> {
+        self.aov_of(ray, &self.intersect(ray)?)
+    }
+    fn aov_of(&self, ray: &Ray, ixs: &[Intersection]) -> Option<AovSample> {
+        let ixs: Vec<_> = ixs
+            .iter()
+            .filter(|i| i.object.material.volume.is_none())
+            .cloned()
+            .collect();
+        let comps = Computation::new(ray, &hit(ixs.clone())?, &ixs);
+        Some(AovSample {
+            depth: comps.t,
+            normal: comps.normalv,
+            albedo: comps.object.material.color_at(comps.object, &comps.point),
+            object: self
+                .objects
+                .iter()
+                .position(|o| std::ptr::eq(o, comps.object))?,
+            shadowed: self.is_shadowed_at(&comps.over_point, comps.time),
+        })
+    }
+    fn fogged(&self, color: Color, ray: &Ray, t: f64) -> Color {
+        match &self.fog {
+            Some(fog) => fog.apply(color, ray, t),
+            None => color,
+        }
+    }
+    // Single scattering along the segment of `ray` up to `t` inside a volume:
+    // light behind the segment is attenuated, and every step adds light from
+    // the lamp scattered towards the eye unless that step is in shadow. The
+    // lamp's light is itself thinned out on its way through the volume.
+    fn scattered_color(&self, medium: &Object, ray: &Ray, t: f64, behind: Color) -> Color {
+        let volume = medium.material.volume.unwrap();
+        let step = t / VOLUME_STEPS as f64;
+        let step_transmittance = volume.transmittance(step * ray.direction.magnitude());
+        let jitter: f64 = rand::thread_rng().gen();
+        let mut transmittance = 1.0;
+        let mut scattered = BLACK;
+        for ix in 0..VOLUME_STEPS {
+            let point = ray.position((ix as f64 + jitter) * step);
+            if !self.is_shadowed_at(&point, ray.time) {
+                let lit = self.light_through(medium, &volume, &point, ray.time);
+                scattered = scattered
+                    + volume.color
+                        * self.light.intensity
+                        * (lit * transmittance * (1.0 - step_transmittance));
+            }
+            transmittance *= step_transmittance;
+        }
+        behind * transmittance + scattered
+    }
+    // fraction of the lamp's light reaching `point` after crossing the volume
+    // from where it enters `medium`
+    fn light_through(&self, medium: &Object, volume: &Volume, point: &Point, time: f64) -> f64 {
+        let v = self.light.position - *point;
+        let r = Ray::new(*point, v.normalize()).with_time(time);
+        let exit = Intersection::intersects(medium, &r)
+            .into_iter()
+            .flatten()
+            .map(|i| i.t)
+            .filter(|t| *t > 0.0)
+            .fold(f64::INFINITY, f64::min);
+        volume.transmittance(exit.min(v.magnitude()))
     }
     pub fn is_shadowed(&self, point: &Point) -> bool {
+        self.is_shadowed_at(point, 0.0)
+    }
+    pub fn is_shadowed_at(&self, point: &Point, time: f64) -> bool {
         let v = self.light.position - *point;
         let distance = v.magnitude();
         let direction = v.normalize();
-        let r = Ray::new(*point, direction);
+        let r = Ray::new(*point, direction).with_time(time);
+        stats::count_ray(RayKind::Shadow);
         if let Some(ixs) = self.intersect(&r) {
+            // volumes scatter light rather than block it
+            let ixs = ixs
+                .into_iter()
+                .filter(|i| i.object.material.volume.is_none())
+                .collect();
             if let Some(h) = hit(ixs) {
                 if h.t < distance {
                     return true;
@@ -92,15 +242,47 @@ impl World {
         if comps.object.material.reflective == 0.0 {
             return BLACK;
         }
-        let reflect_ray = Ray::new(comps.over_point, comps.reflectv);
-        let color = self.color_at(&reflect_ray, remaining - 1);
+        let roughness = comps
+            .object
+            .material
+            .roughness_at(comps.object, &comps.point);
+        let color = self.color_in_lobe(
+            comps.spawn_ray(comps.over_point, comps.reflectv),
+            RayKind::Reflection,
+            roughness,
+            remaining - 1,
+            |direction| direction.dot_product(&comps.normalv) > 0.0,
+        );
         color * comps.object.material.reflective
     }
     pub fn refracted_color(&self, comps: &Computation, remaining: usize) -> Color {
         if comps.object.material.transparency == 0.0 || remaining == 0 {
             return BLACK;
         }
-        let n_ratio = comps.n1 / comps.n2;
+        if comps.wavelength.is_none() && comps.is_dispersive() {
+            // trace each channel at its own wavelength and keep only that channel
+            let index_at = |medium: Option<&Object>, wavelength: f64| {
+                medium.map_or(1.0, |m| m.material.refractive_index_at(Some(wavelength)))
+            };
+            let [r, g, b] = RGB_WAVELENGTHS.map(|wavelength| {
+                self.refracted_color_at(
+                    comps,
+                    index_at(comps.medium, wavelength) / index_at(comps.next_medium, wavelength),
+                    Some(wavelength),
+                    remaining,
+                )
+            });
+            return Color::new(r.rgb.x, g.rgb.y, b.rgb.z);
+        }
+        self.refracted_color_at(comps, comps.n1 / comps.n2, comps.wavelength, remaining)
+    }
+    fn refracted_color_at(
+        &self,
+        comps: &Computation,
+        n_ratio: f64,
+        wavelength: Option<f64>,
+        remaining: usize,
+    ) -> Color {
         let cos_i = comps.eyev.dot_product(&comps.normalv);
         let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
         if sin2_t > 1.0 {
@@ -108,8 +290,57 @@ impl World {
         }
         let cos_t = (1.0 - sin2_t).sqrt();
         let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
-        let refract_ray = Ray::new(comps.under_point, direction);
-        self.color_at(&refract_ray, remaining - 1) * comps.object.material.transparency
+        let roughness = comps
+            .object
+            .material
+            .roughness_at(comps.object, &comps.point);
+        let color = self.color_in_lobe(
+            comps
+                .spawn_ray(comps.under_point, direction)
+                .with_wavelength(wavelength),
+            RayKind::Refraction,
+            roughness,
+            remaining - 1,
+            |direction| direction.dot_product(&comps.normalv) < 0.0,
+        );
+        color * comps.object.material.transparency
+    }
+    // Averages rays scattered around the direction of `ray` within a lobe that
+    // widens with roughness. Only the first bounce spreads into several rays,
+    // deeper ones trace a single ray and leave the averaging to the pixel
+    // samples. Samples rejected by `valid` (e.g. reflections pointing into the
+    // surface) are absorbed.
+    fn color_in_lobe(
+        &self,
+        ray: Ray,
+        kind: RayKind,
+        roughness: f64,
+        remaining: usize,
+        valid: impl Fn(&Vector) -> bool,
+    ) -> Color {
+        if roughness <= 0.0 {
+            stats::count_ray(kind);
+            return self.color_at(&ray, remaining);
+        }
+        let samples = match remaining + 1 >= REMAINING {
+            true => GLOSSY_SAMPLES,
+            false => 1,
+        };
+        let direction = ray.direction.normalize();
+        let mut color = BLACK;
+        for _ in 0..samples {
+            let sample = (direction + random_in_unit_sphere() * roughness).normalize();
+            if !valid(&sample) {
+                continue;
+            }
+            let sample_ray = Ray {
+                direction: sample,
+                ..ray
+            };
+            stats::count_ray(kind);
+            color = color + self.color_at(&sample_ray, remaining);
+        }
+        color * (1.0 / samples as f64)
     }
 }
 
@@ -139,8 +370,12 @@ impl Default for World {
 #[cfg(test)]
 mod world_tests {
 
+    use std::f64::consts::PI;
+
     use crate::features::{
-        computation::Computation, transformations::translation, vector::Vector, Pattern,
+        computation::Computation,
+        transformations::{rotation_z, translation},
+        Dispersion, Pattern, GREY, WHITE,
     };
 
     use super::*;
@@ -483,4 +718,224 @@ mod world_tests {
             Color::new(0.9339151412754023, 0.696434227200244, 0.692430691912747)
         )
     }
+    #[test]
+    fn rough_reflection_of_uniform_surroundings() {
+        let mut w = World::new(Light::default());
+        let dome = Object::sphere_builder()
+            .transformation(scaling(20.0, 20.0, 20.0))
+            .material(Material {
+                ambient: 1.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                ..Default::default()
+            })
+            .build();
+        let floor = Object::plane_builder()
+            .transformation(translation(0.0, -1.0, 0.0))
+            .material(Material {
+                reflective: 0.5,
+                roughness: 0.4,
+                ..Default::default()
+            })
+            .build();
+        w.add_shapes(vec![dome, floor]);
+        let r = Ray::new(
+            Point::new(0.0, 0.0, -3.0),
+            Vector::new(0.0, -(2.0_f64.sqrt() / 2.0), 2.0_f64.sqrt() / 2.0),
+        );
+        let i = Intersection::new(2.0_f64.sqrt(), &w.objects[1]);
+        let comps = Computation::new(&r, &i, &[]);
+        let color = w.reflected_color(&comps, 1);
+        assert_eq!(color, Color::new(0.5, 0.5, 0.5));
+    }
+    #[test]
+    fn only_the_first_rough_bounce_spreads() {
+        let mut w = World::new(Light::default());
+        w.add_shape(
+            Object::plane_builder()
+                .material(Material {
+                    reflective: 0.5,
+                    roughness: 0.4,
+                    ..Default::default()
+                })
+                .build(),
+        );
+        let r = Ray::new(Point::new(0.0, 1.0, -1.0), Vector::new(0.0, -1.0, 1.0));
+        let i = Intersection::new(1.0, &w.objects[0]);
+        let comps = Computation::new(&r, &i, &[]);
+        let reflections = |remaining| {
+            stats::take();
+            w.reflected_color(&comps, remaining);
+            let mut counted = stats::RenderStats::default();
+            counted.add(&stats::take(), REMAINING);
+            counted.rays.reflection
+        };
+        assert_eq!(reflections(REMAINING), GLOSSY_SAMPLES as u64);
+        assert_eq!(reflections(REMAINING - 1), 1);
+    }
+    #[test]
+    fn color_seen_through_absorbing_medium() {
+        let mut w = World::new(Light::default());
+        let bottle = Object::sphere_builder()
+            .material(Material {
+                ambient: 1.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                absorption: Color::new(1.0, 0.5, 0.5),
+                absorption_density: 1.0,
+                ..Default::default()
+            })
+            .build();
+        w.add_shape(bottle);
+        let r = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        let c = w.color_at(&r, 4);
+        assert_eq!(c, Color::new(1.0, 0.5, 0.5));
+    }
+    #[test]
+    fn dispersive_refraction_splits_channels() {
+        let mut w = World::default();
+        // grey level rising with height, the same in every channel
+        let mut height = Pattern::gradient(BLACK, WHITE);
+        height.set_transformation(
+            translation(0.0, -1.0, 0.0) * rotation_z(PI / 2.0) * scaling(2.0, 2.0, 2.0),
+        );
+        w.objects[0].material.ambient = 1.0;
+        w.objects[0].material.pattern = Some(height);
+        w.objects[1].material.transparency = 1.0;
+        let r = Ray::new(
+            Point::new(0.0, 0.0, -5.0),
+            Vector::new(0.0, 0.05, 1.0).normalize(),
+        );
+        let mut refracted = |index: f64, dispersion: Option<Dispersion>| {
+            w.objects[1].material.refractive_index = index;
+            w.objects[1].material.dispersion = dispersion;
+            let xs = w.intersect(&r).unwrap();
+            let comps = Computation::new(&r, &xs[1], &xs);
+            w.refracted_color(&comps, 5)
+        };
+        let low = refracted(1.5, None).rgb.x;
+        let high = refracted(1.6, None).rgb.x;
+        assert_ne!(low, high);
+        // red has the lower index under normal dispersion and the higher one
+        // when the Cauchy B term is negative
+        let normal = refracted(1.5, Some(Dispersion::Cauchy(1.5, 0.02)));
+        assert_eq!(
+            (normal.rgb.x - normal.rgb.z).signum(),
+            (low - high).signum()
+        );
+        let anomalous = refracted(1.5, Some(Dispersion::Cauchy(1.6, -0.02)));
+        assert_eq!(
+            (anomalous.rgb.x - anomalous.rgb.z).signum(),
+            (high - low).signum()
+        );
+    }
+    #[test]
+    fn fog_colors_rays_that_miss() {
+        let w = World {
+            fog: Some(Fog::new(GREY, 0.1, 0.0)),
+            ..Default::default()
+        };
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(&r, 4), GREY);
+    }
+    #[test]
+    fn fog_fades_distant_hits() {
+        let mut w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let clear = w.color_at(&r, 4);
+        w.fog = Some(Fog::new(BLACK, 0.1, 0.0));
+        assert_eq!(w.color_at(&r, 4), clear * (-0.4_f64).exp());
+    }
+    #[test]
+    fn light_scatters_inside_volume() {
+        let mut w = World::new(Light::default());
+        let smoke = Object::sphere_builder()
+            .material(Material::builder().volume(Volume::new(WHITE, 1.0)).build())
+            .build();
+        w.add_shape(smoke);
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let c = w.color_at(&r, 4);
+        // the light reaching each step has crossed part of the sphere
+        let unattenuated = 1.0 - (-2.0_f64).exp();
+        assert!(c.rgb.x > 0.0 && c.rgb.x < unattenuated);
+        assert_eq!(c.rgb.x, c.rgb.z);
+        assert!(!w.is_shadowed(&Point::new(0.0, 0.0, 5.0)));
+        let centre = w.light_through(
+            &w.objects[0],
+            &Volume::new(WHITE, 1.0),
+            &Point::default(),
+            0.0,
+        );
+        assert_eq!(centre, (-1.0_f64).exp());
+    }
+    #[test]
+    fn crossing_a_volume_boundary_costs_a_bounce() {
+        let mut w = World::new(Light::default());
+        w.background = Background::Color(WHITE);
+        w.add_shape(
+            Object::sphere_builder()
+                .material(Material::builder().volume(Volume::new(WHITE, 0.0)).build())
+                .build(),
+        );
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(w.color_at(&r, 2), WHITE);
+        assert_eq!(w.color_at(&r, 1), BLACK);
+    }
+    #[test]
+    fn missed_rays_see_the_background() {
+        let w = World {
+            background: Background::Gradient(BLACK, WHITE),
+            ..Default::default()
+        };
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(&r, 4), WHITE);
+    }
+    #[test]
+    fn background_is_visible_in_reflections() {
+        let mut w = World::new(Light::default());
+        w.background = Background::Color(GREY);
+        let mirror = Object::plane_builder()
+            .material(Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                reflective: 1.0,
+                ..Default::default()
+            })
+            .build();
+        w.add_shape(mirror);
+        let r = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 1.0));
+        assert_eq!(w.color_at(&r, 4), GREY);
+    }
+    #[test]
+    fn image_based_lighting_brightens_open_surfaces() {
+        let mut w = World::new(Light::new(Point::new(0.0, -10.0, 0.0), WHITE));
+        w.background = Background::Color(WHITE);
+        w.image_based_lighting = true;
+        w.add_shape(
+            Object::plane_builder()
+                .material(Material {
+                    ambient: 0.0,
+                    diffuse: 0.5,
+                    ..Default::default()
+                })
+                .build(),
+        );
+        let r = Ray::new(Point::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0));
+        assert_eq!(w.color_at(&r, 4), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn aov_of_the_first_surface() {
+        let w = World::default();
+        let r = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let aov = w.aov_at(&r).unwrap();
+        assert_eq!(aov.depth, 4.0);
+        assert_eq!(aov.normal, Vector::new(0.0, 0.0, -1.0));
+        assert_eq!(aov.albedo, Color::new(0.8, 1.0, 0.6));
+        assert_eq!(aov.object, 0);
+        assert!(!aov.shadowed);
+        let miss = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 1.0, 0.0));
+        assert_eq!(w.aov_at(&miss), None);
+    }
 }
@@ -1,37 +1,68 @@
 pub mod features {
+    pub use aov::*;
+    pub use background::*;
     pub use camera::*;
     pub use canvas::*;
     pub use color::*;
     pub use computation::*;
     pub use consts::*;
+    pub use denoise::*;
+    pub use film::*;
+    pub use filter::*;
     pub use intersection::*;
     pub use light::*;
     pub use material::*;
     pub use matrice::*;
+    pub use media::*;
     pub use object::*;
     pub use pattern::*;
     pub use point::*;
     pub use ray::*;
     pub use shape::*;
+    pub use stats::*;
+    pub use tile::*;
     pub use transformations::*;
     pub use vector::*;
     pub use world::*;
+    mod aov;
+    mod background;
     mod camera;
     mod canvas;
     mod color;
     mod computation;
     mod consts;
+    mod denoise;
+    mod film;
+    mod filter;
     mod intersection;
     mod light;
     mod material;
     mod matrice;
+    mod media;
     mod object;
     mod pattern;
     mod point;
     mod ray;
+    mod sampling;
     mod shape;
+    mod stats;
+    mod tile;
     mod transformations;
     mod tuple;
     mod vector;
     mod world;
 }
+pub mod scene {
+    pub use animation::*;
+    pub use config::*;
+    pub use error::*;
+    pub use format::*;
+    pub use schema::*;
+    mod animation;
+    mod config;
+    mod definitions;
+    mod error;
+    mod format;
+    mod include;
+    mod schema;
+}
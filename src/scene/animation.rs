@@ -0,0 +1,236 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use super::{config::Config, error::ConfigError, schema};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AnimationConfig {
+    pub start_frame: Option<u32>,
+    pub end_frame: Option<u32>,
+    pub step: Option<u32>,
+    pub output_dir: Option<String>,
+    pub tracks: Vec<TrackConfig>,
+}
+
+impl AnimationConfig {
+    pub fn frames(&self) -> impl Iterator<Item = u32> {
+        let start = self.start_frame.unwrap_or(1);
+        let end = self.end_frame.unwrap_or_else(|| {
+            self.tracks
+                .iter()
+                .flat_map(|track| track.keys.iter().map(|key| key.frame))
+                .max()
+                .unwrap_or(start)
+        });
+        (start..=end).step_by(self.step.unwrap_or(1).max(1) as usize)
+    }
+
+    pub fn check(&self, base: &Config) -> Result<(), ConfigError> {
+        let first = self.start_frame.unwrap_or(1);
+        self.frame(base, first).map(|_| ())
+    }
+
+    // the base scene with every track applied at `frame`
+    pub fn frame(&self, base: &Config, frame: u32) -> Result<Config, ConfigError> {
+        let mut scene = serde_yaml::to_value(base).unwrap();
+        for (i, track) in self.tracks.iter().enumerate() {
+            let located = |e: ConfigError| {
+                e.within(&format!("[{}]", i))
+                    .within("tracks")
+                    .within("animation")
+            };
+            let value = track
+                .value_at(frame as f64)
+                .ok_or_else(|| located(ConfigError::new("keys", "needs at least one keyframe")))?;
+            set_path(&mut scene, &track.path, value).ok_or_else(|| {
+                located(ConfigError::new(
+                    "path",
+                    &format!("no value at `{}`", track.path),
+                ))
+            })?;
+        }
+        serde_yaml::from_value(scene).map_err(|e| {
+            ConfigError::new("animation", &format!("frame {} is invalid: {}", frame, e))
+        })
+    }
+}
+
+pub(crate) const EASINGS: &[&str] = &["linear", "ease_in", "ease_out", "ease_in_out"];
+
+// a track animates one value of the scene, addressed by a path such as
+// `camera.from`, `light.position` or `objects[2].transformation[0].rad`
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TrackConfig {
+    pub path: String,
+    #[schemars(schema_with = "schema::easings", default)]
+    pub easing: Option<String>,
+    pub keys: Vec<KeyframeConfig>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KeyframeConfig {
+    pub frame: u32,
+    #[schemars(with = "serde_json::Value")]
+    pub value: Value,
+}
+
+impl TrackConfig {
+    // the value at `frame`, held before the first and after the last key;
+    // none for a track without keys
+    pub fn value_at(&self, frame: f64) -> Option<Value> {
+        let mut keys = self.keys.clone();
+        keys.sort_by_key(|key| key.frame);
+        let next = keys.iter().position(|key| key.frame as f64 > frame);
+        Some(match next {
+            None => keys.last()?.value.clone(),
+            Some(0) => keys[0].value.clone(),
+            Some(i) => {
+                let (a, b) = (&keys[i - 1], &keys[i]);
+                let t = (frame - a.frame as f64) / (b.frame - a.frame) as f64;
+                interpolate(&a.value, &b.value, self.ease(t))
+            }
+        })
+    }
+
+    fn ease(&self, t: f64) -> f64 {
+        match self.easing.as_deref() {
+            Some("ease_in") => t * t,
+            Some("ease_out") => t * (2.0 - t),
+            Some("ease_in_out") => t * t * (3.0 - 2.0 * t),
+            _ => t,
+        }
+    }
+}
+
+// numbers and lists of numbers are interpolated, anything else steps
+fn interpolate(a: &Value, b: &Value, t: f64) -> Value {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            let (x, y) = (x.as_f64().unwrap(), y.as_f64().unwrap());
+            Value::from(x + (y - x) * t)
+        }
+        (Value::Sequence(x), Value::Sequence(y)) if x.len() == y.len() => Value::Sequence(
+            x.iter()
+                .zip(y.iter())
+                .map(|(x, y)| interpolate(x, y, t))
+                .collect(),
+        ),
+        _ if t < 1.0 => a.clone(),
+        _ => b.clone(),
+    }
+}
+
+fn set_path(scene: &mut Value, path: &str, value: Value) -> Option<()> {
+    let mut current = scene;
+    for segment in path.split('.') {
+        let (key, indices) = segment.split_once('[').unwrap_or((segment, ""));
+        if !key.is_empty() {
+            if current.is_null() {
+                *current = Value::Mapping(Mapping::new());
+            }
+            let map = current.as_mapping_mut()?;
+            current = map.entry(Value::from(key)).or_insert(Value::Null);
+        }
+        for index in indices.split('[').filter(|i| !i.is_empty()) {
+            let index: usize = index.strip_suffix(']')?.parse().ok()?;
+            current = current.as_sequence_mut()?.get_mut(index)?;
+        }
+    }
+    *current = value;
+    Some(())
+}
+
+#[cfg(test)]
+mod animation_tests {
+    use super::*;
+
+    fn track(easing: Option<&str>, keys: &[(u32, &str)]) -> TrackConfig {
+        TrackConfig {
+            path: "fog.density".to_string(),
+            easing: easing.map(str::to_string),
+            keys: keys
+                .iter()
+                .map(|(frame, value)| KeyframeConfig {
+                    frame: *frame,
+                    value: serde_yaml::from_str(value).unwrap(),
+                })
+                .collect(),
+        }
+    }
+
+    fn number(value: Option<Value>) -> f64 {
+        value.unwrap().as_f64().unwrap()
+    }
+
+    #[test]
+    fn values_are_interpolated_between_keys() {
+        let numbers = track(None, &[(11, "10"), (1, "0")]);
+        assert_eq!(number(numbers.value_at(6.0)), 5.0);
+        let list = track(None, &[(0, "[0, 2, 4]"), (4, "[4, 2, 0]")]);
+        let value = serde_yaml::to_string(&list.value_at(1.0).unwrap()).unwrap();
+        assert_eq!(value, "- 1.0\n- 2.0\n- 3.0\n");
+    }
+
+    #[test]
+    fn easing_shapes_the_interpolation() {
+        let keys = [(0, "0"), (10, "1")];
+        assert_eq!(number(track(Some("ease_in"), &keys).value_at(5.0)), 0.25);
+        assert_eq!(number(track(Some("ease_out"), &keys).value_at(5.0)), 0.75);
+        assert_eq!(number(track(Some("ease_in_out"), &keys).value_at(5.0)), 0.5);
+    }
+
+    #[test]
+    fn values_hold_outside_the_keys() {
+        let held = track(None, &[(5, "1"), (10, "2")]);
+        assert_eq!(number(held.value_at(0.0)), 1.0);
+        assert_eq!(number(held.value_at(20.0)), 2.0);
+        assert_eq!(track(None, &[]).value_at(0.0), None);
+    }
+
+    #[test]
+    fn other_values_step_at_the_next_key() {
+        let shapes = track(None, &[(0, "cube"), (10, "sphere")]);
+        assert_eq!(shapes.value_at(9.0), Some(Value::from("cube")));
+        assert_eq!(shapes.value_at(10.0), Some(Value::from("sphere")));
+    }
+
+    #[test]
+    fn frames_run_to_the_last_key() {
+        let animation = AnimationConfig {
+            step: Some(2),
+            tracks: vec![track(None, &[(0, "0"), (6, "1")])],
+            ..Default::default()
+        };
+        assert_eq!(animation.frames().collect::<Vec<_>>(), vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn tracks_set_values_in_the_scene() {
+        let animation = AnimationConfig {
+            tracks: vec![track(None, &[(1, "0.5")])],
+            ..Default::default()
+        };
+        let scene = animation.frame(&Config::default(), 1).unwrap();
+        assert_eq!(scene.fog.unwrap().density, Some(0.5));
+    }
+
+    #[test]
+    fn bad_tracks_are_located() {
+        let mut animation = AnimationConfig {
+            tracks: vec![track(None, &[(1, "0.5")]), track(None, &[])],
+            ..Default::default()
+        };
+        let e = animation.check(&Config::default()).unwrap_err();
+        assert_eq!(e.path, "animation.tracks[1].keys");
+        animation.tracks[0].path = "objects[3].shape".to_string();
+        let e = animation.check(&Config::default()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "animation.tracks[0].path: no value at `objects[3].shape`"
+        );
+    }
+}
@@ -0,0 +1,1445 @@
+use std::{
+    collections::BTreeMap,
+    f64::consts::PI,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::features::{
+    look_at, rotation, rotation_x, rotation_y, rotation_z, scaling, shearing, translation,
+    view_transformation, Aov, Background, Camera, Color, Denoiser, Dispersion, EnvironmentMap,
+    Filter, Fog, Light, Material, Matrice, Object, Pattern, PatternType, Point, Projection,
+    Shading, Shape, Sky, TileOptions, TileOrder, Vector, Volume, World, WHITE,
+};
+
+use super::{
+    animation::AnimationConfig,
+    definitions::Definitions,
+    error::{required, ConfigError},
+    format::Format,
+    include::{self, Files},
+    schema,
+};
+
+const NOT_LOADED: &str = "only followed when the scene is loaded from a file";
+
+// accepted values of the string fields, shared by the error messages and
+// the schema
+pub(crate) const TILE_ORDERS: &[&str] = &["scanline", "spiral", "hilbert"];
+pub(crate) const STATS: &[&str] = &["print", "json"];
+pub(crate) const BACKGROUND_TYPES: &[&str] = &["color", "gradient", "sky", "environment"];
+pub(crate) const PROJECTIONS: &[&str] =
+    &["perspective", "orthographic", "fisheye", "equirectangular"];
+pub(crate) const FILTER_TYPES: &[&str] = &["box", "tent", "gaussian", "mitchell", "lanczos"];
+pub(crate) const SHADINGS: &[&str] = &["phong", "microfacet"];
+pub(crate) const PATTERN_TYPES: &[&str] = &["checker", "stripe", "gradient", "ring"];
+pub(crate) const SHAPE_TYPES: &[&str] = &["sphere", "cone", "cube", "cylinder", "plane"];
+pub(crate) const TRANSFORMATION_TYPES: &[&str] = &[
+    "translation",
+    "scaling",
+    "rotation_x",
+    "rotation_y",
+    "rotation_z",
+    "rotation",
+    "shearing",
+    "look_at",
+    "matrix",
+];
+
+// camera defaults, also published in the schema
+const DEFAULT_FROM: TupleConfig = TupleConfig(3.0, 8.5, -14.5);
+const DEFAULT_TO: TupleConfig = TupleConfig(0.0, 0.0, 0.0);
+const DEFAULT_UP: TupleConfig = TupleConfig(0.0, 1.0, 0.0);
+const DEFAULT_SIZE: i32 = 1200;
+const DEFAULT_FOV: f64 = PI / 3.5;
+
+fn default_from() -> Option<TupleConfig> {
+    Some(DEFAULT_FROM)
+}
+
+fn default_to() -> Option<TupleConfig> {
+    Some(DEFAULT_TO)
+}
+
+fn default_up() -> Option<TupleConfig> {
+    Some(DEFAULT_UP)
+}
+
+fn default_size() -> Option<i32> {
+    Some(DEFAULT_SIZE)
+}
+
+fn default_fov() -> Option<f64> {
+    Some(DEFAULT_FOV)
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Other scene files, relative to this one, layered underneath it
+    pub include: Option<Vec<String>>,
+    pub light: Option<LightConfig>,
+    /// Camera settings
+    pub camera: Option<CameraConfig>,
+    /// Named patterns that patterns can `extend`
+    pub patterns: Option<BTreeMap<String, PatternConfig>>,
+    /// Named materials that materials can `extend`
+    pub materials: Option<BTreeMap<String, MaterialConfig>>,
+    /// Named objects that objects can `extend`
+    pub define: Option<BTreeMap<String, ObjectConfig>>,
+    pub objects: Option<Vec<ObjectConfig>>,
+    pub fog: Option<FogConfig>,
+    pub background: Option<BackgroundConfig>,
+    pub image_based_lighting: Option<bool>,
+    /// Filename for output image
+    pub output_file: Option<String>,
+    pub animation: Option<AnimationConfig>,
+    pub render: Option<RenderConfig>,
+}
+
+impl Config {
+    // reads a scene file along with everything it includes or instances,
+    // in the format its extension names
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        include::load(path.as_ref(), &mut Files::default())
+    }
+
+    // like `load`, and adds every file the scene names to `files`, even when
+    // it fails to load, so they can be watched for changes
+    pub fn load_recording<P: AsRef<Path>>(
+        path: P,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<Config, ConfigError> {
+        let mut read = Files::default();
+        let config = include::load(path.as_ref(), &mut read);
+        files.extend(read.read);
+        config
+    }
+
+    // a scene that is not in a file, such as one piped to the renderer, with
+    // includes relative to `dir`
+    pub fn load_text(text: &str, format: Format, dir: &Path) -> Result<Config, ConfigError> {
+        include::expand(text, format, dir, &mut Files::default())
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Config, ConfigError> {
+        Format::Yaml.parse(text)
+    }
+
+    // unset fields are left out rather than written as nulls
+    pub fn to_yaml(&self) -> String {
+        let value = without_nulls(serde_yaml::to_value(self).unwrap());
+        serde_yaml::to_string(&value).unwrap()
+    }
+
+    // a scene description that loads back into the same world; the camera
+    // and render settings are left for the caller to fill in. Fails on
+    // objects that scene files cannot describe
+    pub fn from_world(world: &World) -> Result<Config, ConfigError> {
+        let objects = world
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(i, object)| {
+                ObjectConfig::try_from(object)
+                    .map_err(|e| e.within(&format!("[{}]", i)).within("objects"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Config {
+            light: Some(LightConfig::from(&world.light)),
+            objects: Some(objects),
+            fog: world.fog.as_ref().map(FogConfig::from),
+            background: Some(BackgroundConfig::from(&world.background)),
+            image_based_lighting: Some(world.image_based_lighting),
+            ..Default::default()
+        })
+    }
+
+    // everything a render needs from the scene, without rendering it
+    pub fn check(&self) -> Result<(), ConfigError> {
+        self.tile_options()?;
+        self.aovs()?;
+        self.denoiser()?;
+        if let Some(animation) = &self.animation {
+            animation.check(self)?;
+        }
+        self.clone().build()?;
+        Ok(())
+    }
+
+    pub fn tile_options(&self) -> Result<TileOptions, ConfigError> {
+        let options = match self.render.clone() {
+            Some(render) => TileOptions::try_from(render).map_err(|e| e.within("render"))?,
+            None => TileOptions::default(),
+        };
+        Ok(TileOptions {
+            scene_hash: self.scene_hash(),
+            ..options
+        })
+    }
+
+    // identifies what the scene looks like, leaving out how and where it is
+    // rendered; FNV-1a, unlike the standard hasher, is the same in every build
+    pub fn scene_hash(&self) -> u64 {
+        let scene = Config {
+            render: None,
+            output_file: None,
+            ..self.clone()
+        };
+        scene
+            .to_yaml()
+            .bytes()
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    pub fn aovs(&self) -> Result<Vec<Aov>, ConfigError> {
+        let names = self.render.as_ref().and_then(|r| r.aovs.clone());
+        names
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let names: Vec<_> = Aov::ALL.iter().map(Aov::name).collect();
+                Aov::from_name(name).ok_or_else(|| {
+                    ConfigError::unknown(&format!("aovs[{}]", i), name, &names).within("render")
+                })
+            })
+            .collect()
+    }
+
+    pub fn denoiser(&self) -> Result<Option<Denoiser>, ConfigError> {
+        let denoise = self.render.as_ref().and_then(|r| r.denoise.clone());
+        denoise
+            .map(|d| Denoiser::try_from(d).map_err(|e| e.within("denoise").within("render")))
+            .transpose()
+    }
+
+    // a quick draft of the scene at a fraction of its resolution, with one
+    // sample per pixel and no checkpoints, passes or denoising
+    pub fn preview(&self, divisor: i32) -> Config {
+        let mut preview = self.clone();
+        let camera = preview.camera.get_or_insert_with(CameraConfig::default);
+        let size = |value: Option<i32>| Some((value.unwrap_or(DEFAULT_SIZE) / divisor).max(1));
+        camera.width = size(camera.width);
+        camera.height = size(camera.height);
+        camera.samples = Some(1);
+        if let Some(render) = &mut preview.render {
+            render.checkpoint = None;
+            render.aovs = None;
+            render.denoise = None;
+        }
+        preview
+    }
+
+    // the scene with every `extend` replaced by the fields it refers to
+    pub fn resolve(mut self) -> Result<Config, ConfigError> {
+        if self.include.is_some() {
+            return Err(ConfigError::new("include", NOT_LOADED));
+        }
+        let definitions = Definitions::new(&self)?;
+        let objects = self.objects.take().unwrap_or_default();
+        let objects = objects
+            .into_iter()
+            .enumerate()
+            .map(|(i, object)| {
+                let object = match object.instance {
+                    Some(_) => Err(ConfigError::new("instance", NOT_LOADED)),
+                    None => definitions.object(object),
+                };
+                object.map_err(|e| e.within(&format!("[{}]", i)).within("objects"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Config {
+            patterns: None,
+            materials: None,
+            define: None,
+            objects: Some(objects),
+            ..self
+        })
+    }
+
+    pub fn build(self) -> Result<(World, Camera), ConfigError> {
+        let config = self.resolve()?;
+        let light = config.light.map_or(Light::default(), Light::from);
+        let camera = match config.camera {
+            Some(camera) => Camera::try_from(camera).map_err(|e| e.within("camera"))?,
+            None => Camera::default(),
+        };
+        let mut world = World::new(light);
+        for (i, object) in config.objects.unwrap_or_default().into_iter().enumerate() {
+            let object = Object::try_from(object)
+                .map_err(|e| e.within(&format!("[{}]", i)).within("objects"))?;
+            world.add_shape(object);
+        }
+        world.fog = config.fog.map(Fog::from);
+        world.background = match config.background {
+            Some(background) => {
+                Background::try_from(background).map_err(|e| e.within("background"))?
+            }
+            None => Background::default(),
+        };
+        world.image_based_lighting = config.image_based_lighting.unwrap_or_default();
+        Ok((world, camera))
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RenderConfig {
+    pub tile_size: Option<usize>,
+    #[schemars(schema_with = "schema::tile_orders", default)]
+    pub tile_order: Option<String>,
+    pub checkpoint: Option<String>,
+    /// Seconds between checkpoint writes
+    pub checkpoint_interval: Option<u64>,
+    /// "print" for a readable report, "json" for machine-readable output
+    #[schemars(schema_with = "schema::stats", default)]
+    pub stats: Option<String>,
+    /// Auxiliary passes written next to the image
+    #[schemars(schema_with = "schema::aovs", default)]
+    pub aovs: Option<Vec<String>>,
+    pub denoise: Option<DenoiseConfig>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DenoiseConfig {
+    pub radius: Option<usize>,
+    pub sigma_spatial: Option<f64>,
+    pub sigma_color: Option<f64>,
+    pub sigma_normal: Option<f64>,
+    pub sigma_albedo: Option<f64>,
+}
+
+impl TryFrom<DenoiseConfig> for Denoiser {
+    type Error = ConfigError;
+
+    fn try_from(value: DenoiseConfig) -> Result<Self, ConfigError> {
+        let default = Denoiser::default();
+        // the filter divides by every sigma
+        let sigma = |value: Option<f64>, default: f64, field: &str| match value {
+            Some(sigma) if sigma <= 0.0 || sigma.is_nan() => {
+                Err(ConfigError::new(field, "must be greater than 0"))
+            }
+            value => Ok(value.unwrap_or(default)),
+        };
+        Ok(Denoiser {
+            radius: value.radius.unwrap_or(default.radius),
+            sigma_spatial: sigma(value.sigma_spatial, default.sigma_spatial, "sigma_spatial")?,
+            sigma_color: sigma(value.sigma_color, default.sigma_color, "sigma_color")?,
+            sigma_normal: sigma(value.sigma_normal, default.sigma_normal, "sigma_normal")?,
+            sigma_albedo: sigma(value.sigma_albedo, default.sigma_albedo, "sigma_albedo")?,
+        })
+    }
+}
+
+impl TryFrom<RenderConfig> for TileOptions {
+    type Error = ConfigError;
+
+    fn try_from(value: RenderConfig) -> Result<Self, ConfigError> {
+        let default = TileOptions::default();
+        Ok(TileOptions {
+            tile_size: value.tile_size.unwrap_or(default.tile_size),
+            order: match value.tile_order.as_deref() {
+                None | Some("scanline") => TileOrder::Scanline,
+                Some("spiral") => TileOrder::Spiral,
+                Some("hilbert") => TileOrder::Hilbert,
+                Some(other) => return Err(ConfigError::unknown("tile_order", other, TILE_ORDERS)),
+            },
+            checkpoint: value.checkpoint.map(Into::into),
+            checkpoint_interval: value
+                .checkpoint_interval
+                .map_or(default.checkpoint_interval, Duration::from_secs),
+            ..default
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LightConfig {
+    pub position: Option<TupleConfig>,
+    pub color: Option<TupleConfig>,
+}
+
+impl From<LightConfig> for Light {
+    fn from(value: LightConfig) -> Self {
+        Light::new(
+            value.position.map_or(Point::default(), Point::from),
+            value.color.map_or(WHITE, Color::from),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FogConfig {
+    pub color: Option<TupleConfig>,
+    pub density: Option<f64>,
+    pub height_falloff: Option<f64>,
+}
+
+impl From<FogConfig> for Fog {
+    fn from(value: FogConfig) -> Self {
+        Fog::new(
+            value.color.map_or(WHITE, Color::from),
+            value.density.unwrap_or(0.05),
+            value.height_falloff.unwrap_or_default(),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BackgroundConfig {
+    #[schemars(schema_with = "schema::background_types", default)]
+    pub background_type: Option<String>,
+    pub color: Option<TupleConfig>,
+    pub bottom: Option<TupleConfig>,
+    pub top: Option<TupleConfig>,
+    pub sun_direction: Option<TupleConfig>,
+    pub turbidity: Option<f64>,
+    pub path: Option<String>,
+}
+
+impl TryFrom<BackgroundConfig> for Background {
+    type Error = ConfigError;
+
+    fn try_from(value: BackgroundConfig) -> Result<Self, ConfigError> {
+        let background_type = required(value.background_type, "background_type")?;
+        Ok(match background_type.as_str() {
+            "color" => Background::Color(Color::from(required(value.color, "color")?)),
+            "gradient" => Background::Gradient(
+                Color::from(required(value.bottom, "bottom")?),
+                Color::from(required(value.top, "top")?),
+            ),
+            "sky" => Background::Sky(Sky::new(
+                value
+                    .sun_direction
+                    .map_or(Vector::new(0.0, 1.0, 1.0), Vector::from),
+                value.turbidity.unwrap_or(3.0),
+            )),
+            "environment" => {
+                let path = required(value.path, "path")?;
+                Background::Environment(EnvironmentMap::load(&path).map_err(|e| {
+                    ConfigError::new("path", &format!("unable to load `{}`: {}", path, e))
+                })?)
+            }
+            other => {
+                return Err(ConfigError::unknown(
+                    "background_type",
+                    other,
+                    BACKGROUND_TYPES,
+                ))
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+pub struct TupleConfig(pub f64, pub f64, pub f64);
+
+impl From<TupleConfig> for Point {
+    fn from(value: TupleConfig) -> Self {
+        Point::new(value.0, value.1, value.2)
+    }
+}
+
+impl From<TupleConfig> for Vector {
+    fn from(value: TupleConfig) -> Self {
+        Vector::new(value.0, value.1, value.2)
+    }
+}
+
+impl From<TupleConfig> for Color {
+    fn from(value: TupleConfig) -> Self {
+        Color::new(value.0, value.1, value.2)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CameraConfig {
+    /// Position of camera
+    #[schemars(default = "default_from")]
+    pub from: Option<TupleConfig>,
+    /// Position where camera points at
+    #[schemars(default = "default_to")]
+    pub to: Option<TupleConfig>,
+    #[schemars(default = "default_up")]
+    pub up: Option<TupleConfig>,
+    #[schemars(default = "default_size", range(min = 1))]
+    pub width: Option<i32>,
+    #[schemars(default = "default_size", range(min = 1))]
+    pub height: Option<i32>,
+    #[schemars(default = "default_fov")]
+    pub fov_radian: Option<f64>,
+    pub aperture: Option<f64>,
+    /// Distance to the plane in focus [default: distance to `to`]
+    pub focal_distance: Option<f64>,
+    /// Samples per pixel
+    pub samples: Option<usize>,
+    #[schemars(schema_with = "schema::projections", default)]
+    pub projection: Option<String>,
+    pub view_width: Option<f64>,
+    pub shutter_open: Option<f64>,
+    pub shutter_close: Option<f64>,
+    pub filter: Option<FilterConfig>,
+}
+
+impl TryFrom<CameraConfig> for Camera {
+    type Error = ConfigError;
+
+    fn try_from(value: CameraConfig) -> Result<Self, ConfigError> {
+        let size = |size: Option<i32>, field| match size.unwrap_or(DEFAULT_SIZE) {
+            size if size >= 1 => Ok(size as f64),
+            _ => Err(ConfigError::new(field, "must be at least 1")),
+        };
+        let mut res = Camera::new(
+            size(value.width, "width")?,
+            size(value.height, "height")?,
+            value.fov_radian.unwrap_or(DEFAULT_FOV),
+        );
+        let from = Point::from(value.from.unwrap_or(DEFAULT_FROM));
+        let to = Point::from(value.to.unwrap_or(DEFAULT_TO));
+        res.transform = view_transformation(from, to, Vector::from(value.up.unwrap_or(DEFAULT_UP)));
+        res.aperture = value.aperture.unwrap_or_default();
+        // focus on the look-at point unless told otherwise
+        res.focal_distance = value
+            .focal_distance
+            .unwrap_or_else(|| (to - from).magnitude());
+        res.samples = value.samples.unwrap_or(1);
+        res.shutter_open = value.shutter_open.unwrap_or_default();
+        res.shutter_close = value.shutter_close.unwrap_or(res.shutter_open);
+        if let Some(filter) = value.filter {
+            res.filter = Filter::try_from(filter).map_err(|e| e.within("filter"))?;
+        }
+        if let Some(projection) = value.projection {
+            res.set_projection(match projection.as_str() {
+                "perspective" => Projection::Perspective,
+                "orthographic" => Projection::Orthographic(value.view_width.unwrap_or(10.0)),
+                "fisheye" => Projection::Fisheye,
+                "equirectangular" => Projection::Equirectangular,
+                other => return Err(ConfigError::unknown("projection", other, PROJECTIONS)),
+            });
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    #[schemars(schema_with = "schema::filter_types", default)]
+    pub filter_type: Option<String>,
+    pub radius: Option<f64>,
+}
+
+impl TryFrom<FilterConfig> for Filter {
+    type Error = ConfigError;
+
+    fn try_from(value: FilterConfig) -> Result<Self, ConfigError> {
+        let filter_type = required(value.filter_type, "filter_type")?;
+        Ok(match filter_type.as_str() {
+            "box" => Filter::Box(value.radius.unwrap_or(0.5)),
+            "tent" => Filter::Tent(value.radius.unwrap_or(1.0)),
+            "gaussian" => Filter::Gaussian(value.radius.unwrap_or(1.5)),
+            "mitchell" => Filter::Mitchell(value.radius.unwrap_or(2.0)),
+            "lanczos" => Filter::Lanczos(value.radius.unwrap_or(3.0)),
+            other => return Err(ConfigError::unknown("filter_type", other, FILTER_TYPES)),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectConfig {
+    /// A name from `define` whose fields this object starts from
+    pub extend: Option<String>,
+    /// Another scene file whose objects are placed under this transformation
+    pub instance: Option<String>,
+    pub shape: Option<ShapeConfig>,
+    pub material: Option<MaterialConfig>,
+    /// Transformations multiplied out in order, so the last entry acts on the
+    /// object first
+    pub transformation: Option<Vec<TransformationConfig>>,
+    /// Transformation at shutter close, composed the same way; the object
+    /// moves, turns and stretches between the two along the shortest way
+    pub motion: Option<Vec<TransformationConfig>>,
+}
+
+impl TryFrom<ObjectConfig> for Object {
+    type Error = ConfigError;
+
+    fn try_from(value: ObjectConfig) -> Result<Self, ConfigError> {
+        let material = match value.material {
+            Some(material) => Material::try_from(material).map_err(|e| e.within("material"))?,
+            None => Material::default(),
+        };
+        let shape = match value.shape {
+            Some(shape) => Shape::try_from(shape).map_err(|e| e.within("shape"))?,
+            None => Shape::Sphere,
+        };
+        let mut object = Object::new(
+            material,
+            shape,
+            compose(value.transformation, "transformation")?,
+        );
+        if value.motion.is_some() {
+            let motion = compose(value.motion, "motion")?;
+            // turning inside out on the way would flatten the object mid-shutter
+            if (motion.determinant() < 0.0) != (object.transformation().determinant() < 0.0) {
+                return Err(ConfigError::new(
+                    "motion",
+                    "mirrors the object relative to `transformation`",
+                ));
+            }
+            object.set_motion(motion);
+        }
+        Ok(object)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialConfig {
+    /// A name from `materials`
+    pub extend: Option<String>,
+    pub color: Option<TupleConfig>,
+    pub ambient: Option<f64>,
+    pub diffuse: Option<f64>,
+    pub specular: Option<f64>,
+    pub shininess: Option<f64>,
+    pub reflective: Option<f64>,
+    pub transparency: Option<f64>,
+    pub refractive_index: Option<f64>,
+    pub pattern: Option<PatternConfig>,
+    #[schemars(schema_with = "schema::shadings", default)]
+    pub shading: Option<String>,
+    pub roughness: Option<f64>,
+    pub metallic: Option<f64>,
+    pub roughness_map: Option<PatternConfig>,
+    pub metallic_map: Option<PatternConfig>,
+    pub absorption: Option<TupleConfig>,
+    pub absorption_density: Option<f64>,
+    pub dispersion: Option<DispersionConfig>,
+    pub volume: Option<VolumeConfig>,
+}
+
+impl TryFrom<MaterialConfig> for Material {
+    type Error = ConfigError;
+
+    fn try_from(value: MaterialConfig) -> Result<Self, ConfigError> {
+        let mut builder = Material::builder();
+        if let Some(ambient) = value.ambient {
+            builder = builder.ambient(ambient);
+        }
+        if let Some(diffuse) = value.diffuse {
+            builder = builder.diffuse(diffuse);
+        }
+        if let Some(specular) = value.specular {
+            builder = builder.specular(specular);
+        }
+        if let Some(shininess) = value.shininess {
+            builder = builder.shininess(shininess);
+        }
+        if let Some(color) = value.color {
+            builder = builder.color(Color::from(color));
+        }
+        if let Some(pattern) = value.pattern {
+            builder = builder.pattern(Pattern::try_from(pattern).map_err(|e| e.within("pattern"))?)
+        }
+        if let Some(reflective) = value.reflective {
+            builder = builder.reflective(reflective);
+        }
+        if let Some(transparency) = value.transparency {
+            builder = builder.transparency(transparency);
+        }
+        if let Some(refractive_index) = value.refractive_index {
+            builder = builder.refractive_index(refractive_index);
+        }
+        if let Some(shading) = value.shading {
+            builder = builder.shading(match shading.as_str() {
+                "phong" => Shading::Phong,
+                "microfacet" => Shading::Microfacet,
+                other => return Err(ConfigError::unknown("shading", other, SHADINGS)),
+            });
+        }
+        if let Some(roughness) = value.roughness {
+            builder = builder.roughness(roughness);
+        }
+        if let Some(metallic) = value.metallic {
+            builder = builder.metallic(metallic);
+        }
+        if let Some(roughness_map) = value.roughness_map {
+            builder = builder.roughness_map(
+                Pattern::try_from(roughness_map).map_err(|e| e.within("roughness_map"))?,
+            );
+        }
+        if let Some(metallic_map) = value.metallic_map {
+            builder = builder.metallic_map(
+                Pattern::try_from(metallic_map).map_err(|e| e.within("metallic_map"))?,
+            );
+        }
+        if let Some(absorption) = value.absorption {
+            builder = builder.absorption(Color::from(absorption));
+        }
+        if let Some(absorption_density) = value.absorption_density {
+            builder = builder.absorption_density(absorption_density);
+        }
+        if let Some(dispersion) = value.dispersion {
+            builder = builder
+                .dispersion(Dispersion::try_from(dispersion).map_err(|e| e.within("dispersion"))?);
+        }
+        if let Some(volume) = value.volume {
+            builder = builder.volume(Volume::from(volume));
+        }
+        Ok(builder.build())
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeConfig {
+    pub color: Option<TupleConfig>,
+    pub density: Option<f64>,
+}
+
+impl From<VolumeConfig> for Volume {
+    fn from(value: VolumeConfig) -> Self {
+        Volume::new(
+            value.color.map_or(WHITE, Color::from),
+            value.density.unwrap_or(1.0),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DispersionConfig {
+    pub abbe_number: Option<f64>,
+    pub cauchy_a: Option<f64>,
+    pub cauchy_b: Option<f64>,
+}
+
+impl TryFrom<DispersionConfig> for Dispersion {
+    type Error = ConfigError;
+
+    fn try_from(value: DispersionConfig) -> Result<Self, ConfigError> {
+        match (value.abbe_number, value.cauchy_a, value.cauchy_b) {
+            (Some(abbe), None, None) => Ok(Dispersion::Abbe(abbe)),
+            (Some(_), _, _) => Err(ConfigError::new(
+                "abbe_number",
+                "give either `abbe_number` or `cauchy_a` and `cauchy_b`, not both",
+            )),
+            (None, None, None) => Err(ConfigError::new(
+                "abbe_number",
+                "missing value, give `abbe_number` or `cauchy_a` and `cauchy_b`",
+            )),
+            (None, a, b) => Ok(Dispersion::Cauchy(
+                required(a, "cauchy_a")?,
+                required(b, "cauchy_b")?,
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PatternConfig {
+    /// A name from `patterns`
+    pub extend: Option<String>,
+    #[schemars(schema_with = "schema::pattern_types", default)]
+    pub pattern_type: Option<String>,
+    pub color_a: Option<TupleConfig>,
+    pub color_b: Option<TupleConfig>,
+    /// Transformations multiplied out in order, so the last entry acts on the
+    /// pattern first
+    pub transformation: Option<Vec<TransformationConfig>>,
+}
+
+impl TryFrom<PatternConfig> for Pattern {
+    type Error = ConfigError;
+
+    fn try_from(value: PatternConfig) -> Result<Self, ConfigError> {
+        let pattern_type = required(value.pattern_type, "pattern_type")?;
+        let constructor = match pattern_type.as_str() {
+            "checker" => Pattern::checker,
+            "stripe" => Pattern::stripe,
+            "gradient" => Pattern::gradient,
+            "ring" => Pattern::ring,
+            other => return Err(ConfigError::unknown("pattern_type", other, PATTERN_TYPES)),
+        };
+        let mut pattern = constructor(
+            Color::from(required(value.color_a, "color_a")?),
+            Color::from(required(value.color_b, "color_b")?),
+        );
+        if value.transformation.is_some() {
+            pattern.set_transformation(compose(value.transformation, "transformation")?);
+        }
+        Ok(pattern)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ShapeConfig {
+    #[schemars(schema_with = "schema::shape_types", default)]
+    pub shape_type: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub closed: Option<bool>,
+}
+
+impl TryFrom<ShapeConfig> for Shape {
+    type Error = ConfigError;
+
+    fn try_from(value: ShapeConfig) -> Result<Self, ConfigError> {
+        let shape_type = required(value.shape_type, "shape_type")?;
+        Ok(match shape_type.as_str() {
+            "sphere" => Shape::Sphere,
+            "cone" => Shape::Cone(
+                required(value.min, "min")?,
+                required(value.max, "max")?,
+                required(value.closed, "closed")?,
+            ),
+            "cube" => Shape::Cube,
+            "cylinder" => Shape::Cylinder(
+                required(value.min, "min")?,
+                required(value.max, "max")?,
+                required(value.closed, "closed")?,
+            ),
+            "plane" => Shape::Plane,
+            other => return Err(ConfigError::unknown("shape_type", other, SHAPE_TYPES)),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TransformationConfig {
+    #[schemars(schema_with = "schema::transformation_types", default)]
+    pub transformation_type: Option<String>,
+    /// Offsets for translation, factors for scaling
+    pub vec: Option<TupleConfig>,
+    /// Rotation angle in radians
+    pub rad: Option<f64>,
+    /// Rotation angle in degrees, instead of `rad`
+    pub deg: Option<f64>,
+    /// Axis of a general rotation
+    pub axis: Option<TupleConfig>,
+    /// Shearing factors xy, xz, yx, yz, zx and zy, each moving the first
+    /// coordinate in proportion to the second
+    pub shear: Option<[f64; 6]>,
+    /// Where look_at places the object
+    pub from: Option<TupleConfig>,
+    /// The point look_at turns the object's -z axis towards
+    pub to: Option<TupleConfig>,
+    /// The direction look_at leans the object's +y axis towards
+    #[schemars(default = "default_up")]
+    pub up: Option<TupleConfig>,
+    /// Four rows of four values
+    pub matrix: Option<Vec<Vec<f64>>>,
+}
+
+impl TryFrom<TransformationConfig> for Matrice {
+    type Error = ConfigError;
+
+    fn try_from(value: TransformationConfig) -> Result<Self, ConfigError> {
+        let transformation_type =
+            required(value.transformation_type.clone(), "transformation_type")?;
+        Ok(match transformation_type.as_str() {
+            "translation" => {
+                let tuple = required(value.vec, "vec")?;
+                translation(tuple.0, tuple.1, tuple.2)
+            }
+            "scaling" => {
+                let tuple = required(value.vec, "vec")?;
+                scaling(tuple.0, tuple.1, tuple.2)
+            }
+            "rotation_x" => rotation_x(angle(&value)?),
+            "rotation_y" => rotation_y(angle(&value)?),
+            "rotation_z" => rotation_z(angle(&value)?),
+            "rotation" => {
+                let axis = Vector::from(required(value.axis.clone(), "axis")?);
+                if axis.magnitude() == 0.0 {
+                    return Err(ConfigError::new("axis", "must not be zero"));
+                }
+                rotation(axis, angle(&value)?)
+            }
+            "shearing" => {
+                let [xy, xz, yx, yz, zx, zy] = required(value.shear, "shear")?;
+                shearing(xy, xz, yx, yz, zx, zy)
+            }
+            "look_at" => {
+                let from = Point::from(required(value.from, "from")?);
+                let to = Point::from(required(value.to, "to")?);
+                let up = Vector::from(value.up.unwrap_or(DEFAULT_UP));
+                if (to - from).magnitude() == 0.0 {
+                    return Err(ConfigError::new("to", "must differ from `from`"));
+                }
+                if (to - from).cross_product(&up).magnitude() == 0.0 {
+                    return Err(ConfigError::new("up", "must not be parallel to the view"));
+                }
+                look_at(from, to, up)
+            }
+            "matrix" => {
+                let data = required(value.matrix, "matrix")?;
+                if data.len() != 4 || data.iter().any(|row| row.len() != 4) {
+                    return Err(ConfigError::new("matrix", "expected 4 rows of 4 values"));
+                }
+                Matrice { size: 4, data }
+            }
+            other => {
+                return Err(ConfigError::unknown(
+                    "transformation_type",
+                    other,
+                    TRANSFORMATION_TYPES,
+                ))
+            }
+        })
+    }
+}
+
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Mapping(map) => Value::Mapping(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(without_nulls).collect()),
+        other => other,
+    }
+}
+
+// the fields of `value` that differ from `default`
+fn changed<T: PartialEq>(value: T, default: T) -> Option<T> {
+    (value != default).then_some(value)
+}
+
+// a single raw matrix, or nothing for the identity
+fn matrix_config(matrix: &Matrice) -> Option<Vec<TransformationConfig>> {
+    (*matrix != Matrice::identity()).then(|| vec![TransformationConfig::from(matrix)])
+}
+
+impl From<Point> for TupleConfig {
+    fn from(value: Point) -> Self {
+        TupleConfig(value.x(), value.y(), value.z())
+    }
+}
+
+impl From<Vector> for TupleConfig {
+    fn from(value: Vector) -> Self {
+        TupleConfig(value.x(), value.y(), value.z())
+    }
+}
+
+impl From<Color> for TupleConfig {
+    fn from(value: Color) -> Self {
+        TupleConfig(value.rgb.x, value.rgb.y, value.rgb.z)
+    }
+}
+
+impl From<&Light> for LightConfig {
+    fn from(value: &Light) -> Self {
+        LightConfig {
+            position: Some(value.position.into()),
+            color: Some(value.intensity.into()),
+        }
+    }
+}
+
+impl From<&Fog> for FogConfig {
+    fn from(value: &Fog) -> Self {
+        FogConfig {
+            color: Some(value.color.into()),
+            density: Some(value.density),
+            height_falloff: Some(value.height_falloff),
+        }
+    }
+}
+
+impl From<&Background> for BackgroundConfig {
+    fn from(value: &Background) -> Self {
+        let config = |background_type: &str| BackgroundConfig {
+            background_type: Some(background_type.to_string()),
+            ..Default::default()
+        };
+        match value {
+            Background::Color(color) => BackgroundConfig {
+                color: Some((*color).into()),
+                ..config("color")
+            },
+            Background::Gradient(bottom, top) => BackgroundConfig {
+                bottom: Some((*bottom).into()),
+                top: Some((*top).into()),
+                ..config("gradient")
+            },
+            Background::Sky(sky) => BackgroundConfig {
+                sun_direction: Some(sky.sun_direction.into()),
+                turbidity: Some(sky.turbidity),
+                ..config("sky")
+            },
+            Background::Environment(map) => BackgroundConfig {
+                path: map.path.as_ref().map(|p| p.to_string_lossy().into_owned()),
+                ..config("environment")
+            },
+        }
+    }
+}
+
+impl From<&Camera> for CameraConfig {
+    fn from(value: &Camera) -> Self {
+        // recover the eye, gaze and up vector from the view transformation;
+        // its second row is the part of the up vector perpendicular to the
+        // gaze, so adding back any component along the gaze rebuilds it
+        let m = &value.transform.data;
+        let from = &value.transform.inverse() * &Point::new(0.0, 0.0, 0.0);
+        let forward = Vector::new(-m[2][0], -m[2][1], -m[2][2]);
+        let true_up = Vector::new(m[1][0], m[1][1], m[1][2]);
+        let up = true_up + forward * (1.0 - true_up.magnitude().powi(2)).max(0.0).sqrt();
+        let (projection, view_width) = match value.projection() {
+            Projection::Perspective => ("perspective", None),
+            Projection::Orthographic(width) => ("orthographic", Some(width)),
+            Projection::Fisheye => ("fisheye", None),
+            Projection::Equirectangular => ("equirectangular", None),
+        };
+        CameraConfig {
+            from: Some(from.into()),
+            to: Some((from + forward * value.focal_distance).into()),
+            up: Some(up.into()),
+            width: Some(value.hsize as i32),
+            height: Some(value.vsize as i32),
+            fov_radian: Some(value.field_of_view),
+            aperture: Some(value.aperture),
+            focal_distance: Some(value.focal_distance),
+            samples: Some(value.samples),
+            projection: Some(projection.to_string()),
+            view_width,
+            shutter_open: Some(value.shutter_open),
+            shutter_close: Some(value.shutter_close),
+            filter: Some(FilterConfig::from(&value.filter)),
+        }
+    }
+}
+
+impl From<&Filter> for FilterConfig {
+    fn from(value: &Filter) -> Self {
+        let filter_type = match value {
+            Filter::Box(_) => "box",
+            Filter::Tent(_) => "tent",
+            Filter::Gaussian(_) => "gaussian",
+            Filter::Mitchell(_) => "mitchell",
+            Filter::Lanczos(_) => "lanczos",
+        };
+        FilterConfig {
+            filter_type: Some(filter_type.to_string()),
+            radius: Some(value.radius()),
+        }
+    }
+}
+
+impl TryFrom<&Object> for ObjectConfig {
+    type Error = ConfigError;
+
+    fn try_from(value: &Object) -> Result<Self, ConfigError> {
+        Ok(ObjectConfig {
+            extend: None,
+            instance: None,
+            shape: Some(ShapeConfig::try_from(&value.shape).map_err(|e| e.within("shape"))?),
+            material: Some(
+                MaterialConfig::try_from(&value.material).map_err(|e| e.within("material"))?,
+            ),
+            transformation: matrix_config(value.transformation()),
+            motion: value.motion().and_then(matrix_config),
+        })
+    }
+}
+
+fn pattern_config(
+    pattern: &Option<Pattern>,
+    field: &str,
+) -> Result<Option<PatternConfig>, ConfigError> {
+    pattern
+        .as_ref()
+        .map(|p| PatternConfig::try_from(p).map_err(|e| e.within(field)))
+        .transpose()
+}
+
+impl TryFrom<&Material> for MaterialConfig {
+    type Error = ConfigError;
+
+    fn try_from(value: &Material) -> Result<Self, ConfigError> {
+        let default = Material::default();
+        Ok(MaterialConfig {
+            extend: None,
+            color: changed(value.color, default.color).map(TupleConfig::from),
+            ambient: changed(value.ambient, default.ambient),
+            diffuse: changed(value.diffuse, default.diffuse),
+            specular: changed(value.specular, default.specular),
+            shininess: changed(value.shininess, default.shininess),
+            reflective: changed(value.reflective, default.reflective),
+            transparency: changed(value.transparency, default.transparency),
+            refractive_index: changed(value.refractive_index, default.refractive_index),
+            pattern: pattern_config(&value.pattern, "pattern")?,
+            shading: match value.shading {
+                Shading::Phong => None,
+                Shading::Microfacet => Some("microfacet".to_string()),
+            },
+            roughness: changed(value.roughness, default.roughness),
+            metallic: changed(value.metallic, default.metallic),
+            roughness_map: pattern_config(&value.roughness_map, "roughness_map")?,
+            metallic_map: pattern_config(&value.metallic_map, "metallic_map")?,
+            absorption: changed(value.absorption, default.absorption).map(TupleConfig::from),
+            absorption_density: changed(value.absorption_density, default.absorption_density),
+            dispersion: value.dispersion.as_ref().map(DispersionConfig::from),
+            volume: value.volume.as_ref().map(VolumeConfig::from),
+        })
+    }
+}
+
+impl From<&Volume> for VolumeConfig {
+    fn from(value: &Volume) -> Self {
+        VolumeConfig {
+            color: Some(value.color.into()),
+            density: Some(value.density),
+        }
+    }
+}
+
+impl From<&Dispersion> for DispersionConfig {
+    fn from(value: &Dispersion) -> Self {
+        match *value {
+            Dispersion::Abbe(abbe) => DispersionConfig {
+                abbe_number: Some(abbe),
+                ..Default::default()
+            },
+            Dispersion::Cauchy(a, b) => DispersionConfig {
+                cauchy_a: Some(a),
+                cauchy_b: Some(b),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl TryFrom<&Pattern> for PatternConfig {
+    type Error = ConfigError;
+
+    fn try_from(value: &Pattern) -> Result<Self, ConfigError> {
+        let (pattern_type, a, b) = match value.pattern_type() {
+            PatternType::Checker(a, b) => ("checker", a, b),
+            PatternType::Stripe(a, b) => ("stripe", a, b),
+            PatternType::Gradient(a, b) => ("gradient", a, b),
+            PatternType::Ring(a, b) => ("ring", a, b),
+            // only used by tests
+            PatternType::Test => {
+                return Err(ConfigError::new(
+                    "pattern_type",
+                    "the test pattern has no scene representation",
+                ))
+            }
+        };
+        Ok(PatternConfig {
+            extend: None,
+            pattern_type: Some(pattern_type.to_string()),
+            color_a: Some((*a).into()),
+            color_b: Some((*b).into()),
+            transformation: matrix_config(value.transformation()),
+        })
+    }
+}
+
+impl TryFrom<&Shape> for ShapeConfig {
+    type Error = ConfigError;
+
+    fn try_from(value: &Shape) -> Result<Self, ConfigError> {
+        let config = |shape_type: &str| ShapeConfig {
+            shape_type: Some(shape_type.to_string()),
+            ..Default::default()
+        };
+        let bounded = |shape_type: &str, min: f64, max: f64, closed: bool| ShapeConfig {
+            min: Some(min),
+            max: Some(max),
+            closed: Some(closed),
+            ..config(shape_type)
+        };
+        Ok(match *value {
+            Shape::Plane => config("plane"),
+            Shape::Sphere => config("sphere"),
+            Shape::Cube => config("cube"),
+            Shape::Cylinder(min, max, closed) => bounded("cylinder", min, max, closed),
+            Shape::Cone(min, max, closed) => bounded("cone", min, max, closed),
+            Shape::Group(_) => {
+                return Err(ConfigError::new(
+                    "shape_type",
+                    "groups have no scene representation yet",
+                ))
+            }
+        })
+    }
+}
+
+impl From<&Matrice> for TransformationConfig {
+    fn from(value: &Matrice) -> Self {
+        TransformationConfig {
+            transformation_type: Some("matrix".to_string()),
+            matrix: Some(value.data.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+// the angle of a rotation, given in either radians or degrees
+fn angle(value: &TransformationConfig) -> Result<f64, ConfigError> {
+    match (value.rad, value.deg) {
+        (Some(rad), None) => Ok(rad),
+        (None, Some(deg)) => Ok(deg.to_radians()),
+        (None, None) => Err(ConfigError::new(
+            "rad",
+            "missing value, give `rad` or `deg`",
+        )),
+        (Some(_), Some(_)) => Err(ConfigError::new(
+            "deg",
+            "give either `rad` or `deg`, not both",
+        )),
+    }
+}
+
+// multiplies the list out left to right, so the last entry is the first to
+// act on the object: [translation, rotation] spins it in place, then moves it.
+// Rays are traced through the inverse, so a matrix without one is refused
+fn compose(list: Option<Vec<TransformationConfig>>, field: &str) -> Result<Matrice, ConfigError> {
+    let matrix = list.unwrap_or_default().into_iter().enumerate().try_fold(
+        Matrice::default(),
+        |acc, (i, x)| {
+            let m =
+                Matrice::try_from(x).map_err(|e| e.within(&format!("[{}]", i)).within(field))?;
+            Ok(acc * m)
+        },
+    )?;
+    match matrix.determinant() != 0.0 {
+        true => Ok(matrix),
+        false => Err(ConfigError::new(
+            field,
+            "flattens the object and cannot be inverted",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    fn error(yaml: &str) -> ConfigError {
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        config.check().unwrap_err()
+    }
+
+    #[test]
+    fn missing_values_are_located() {
+        let e = error("objects:\n  - shape: {shape_type: sphere}\n  - shape: {shape_type: cone, max: 1, closed: true}\n");
+        assert_eq!(e.path, "objects[1].shape.min");
+        assert_eq!(e.message, "missing value");
+    }
+
+    #[test]
+    fn misspelt_types_get_a_suggestion() {
+        let e = error(
+            "objects:\n  - transformation:\n      - {transformation_type: rotaton_y, rad: 1}\n",
+        );
+        assert_eq!(e.path, "objects[0].transformation[0].transformation_type");
+        assert_eq!(e.suggestion.as_deref(), Some("did you mean `rotation_y`?"));
+        let e = error("camera: {filter: {filter_type: lanczoz}}\n");
+        assert_eq!(e.path, "camera.filter.filter_type");
+        assert_eq!(e.suggestion.as_deref(), Some("did you mean `lanczos`?"));
+    }
+
+    #[test]
+    fn unrelated_types_get_no_suggestion() {
+        let e = error("objects:\n  - shape: {shape_type: torus}\n");
+        assert_eq!(e.path, "objects[0].shape.shape_type");
+        assert_eq!(e.suggestion, None);
+    }
+
+    #[test]
+    fn render_settings_are_checked() {
+        let e = error("render: {aovs: [depth, depht]}\n");
+        assert_eq!(e.path, "render.aovs[1]");
+        assert_eq!(e.suggestion.as_deref(), Some("did you mean `depth`?"));
+        let e = error("render: {denoise: {sigma_color: 0}}\n");
+        assert_eq!(e.path, "render.denoise.sigma_color");
+    }
+
+    #[test]
+    fn image_sizes_are_checked() {
+        let e = error("camera: {width: 0}\n");
+        assert_eq!(e.path, "camera.width");
+        assert_eq!(e.message, "must be at least 1");
+        assert_eq!(error("camera: {height: -5}\n").path, "camera.height");
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        let e = Config::from_yaml("objects: [").unwrap_err();
+        assert_eq!(e.path, "");
+        assert!(Config::load("no/such/scene.yaml").is_err());
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let e = Config::from_yaml("camera:\n  widht: 10\n").unwrap_err();
+        assert!(e.message.starts_with("camera: unknown field `widht`"));
+        assert!(Config::from_yaml("objects:\n  - shape: {shape_type: cube, radius: 1}\n").is_err());
+        let e = Config::from_yaml("objetcs: []\n").unwrap_err();
+        assert!(e.message.starts_with("unknown field `objetcs`"));
+    }
+
+    #[test]
+    fn dispersion_needs_both_cauchy_coefficients() {
+        let e = error("objects:\n  - material: {dispersion: {cauchy_a: 1.5}}\n");
+        assert_eq!(e.path, "objects[0].material.dispersion.cauchy_b");
+        assert_eq!(e.message, "missing value");
+    }
+
+    #[test]
+    fn matrices_need_four_rows_of_four() {
+        let e = error("objects:\n  - transformation:\n      - {transformation_type: matrix, matrix: [[1, 0], [0, 1]]}\n");
+        assert_eq!(e.path, "objects[0].transformation[0].matrix");
+    }
+
+    // the transformation of the only object in a scene
+    fn transformation(yaml: &str) -> Result<Matrice, ConfigError> {
+        let list = format!("objects:\n  - transformation:\n      - {}\n", yaml);
+        let (world, _) = Config::from_yaml(&list)?.build()?;
+        Ok(world.objects.last().unwrap().transformation().clone())
+    }
+
+    #[test]
+    fn rotations_take_degrees_or_an_axis() {
+        let degrees = transformation("{transformation_type: rotation_y, deg: 90}").unwrap();
+        assert_eq!(degrees, rotation_y(PI / 2.0));
+        let axis = transformation("{transformation_type: rotation, axis: [0, 2, 0], rad: 0.5}");
+        assert_eq!(axis.unwrap(), rotation_y(0.5));
+        let e = transformation("{transformation_type: rotation_x, rad: 1, deg: 90}").unwrap_err();
+        assert_eq!(e.path, "objects[0].transformation[0].deg");
+        let e = transformation("{transformation_type: rotation, axis: [0, 0, 0], deg: 9}");
+        assert_eq!(e.unwrap_err().path, "objects[0].transformation[0].axis");
+    }
+
+    #[test]
+    fn shearing_and_look_at_are_supported() {
+        let sheared = transformation("{transformation_type: shearing, shear: [1, 0, 0, 0, 0, 0]}");
+        assert_eq!(sheared.unwrap(), shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0));
+        let placed =
+            transformation("{transformation_type: look_at, from: [0, 0, 5], to: [0, 0, 0]}");
+        assert_eq!(placed.unwrap(), translation(0.0, 0.0, 5.0));
+        let e = transformation("{transformation_type: look_at, from: [0, 0, 0], to: [0, 3, 0]}");
+        assert_eq!(e.unwrap_err().path, "objects[0].transformation[0].up");
+    }
+
+    #[test]
+    fn singular_transformations_are_rejected() {
+        let e = error("objects:\n  - transformation:\n      - {transformation_type: shearing, shear: [1, 0, 1, 0, 0, 0]}\n");
+        assert_eq!(e.path, "objects[0].transformation");
+        let e = error(
+            "objects:\n  - motion:\n      - {transformation_type: scaling, vec: [1, 0, 1]}\n",
+        );
+        assert_eq!(e.path, "objects[0].motion");
+        let e = error(
+            "objects:\n  - motion:\n      - {transformation_type: scaling, vec: [-1, 1, 1]}\n",
+        );
+        assert_eq!(e.path, "objects[0].motion");
+        assert!(e.message.starts_with("mirrors"));
+        let e = error("objects:\n  - material:\n      pattern:\n        pattern_type: ring\n        color_a: [1, 1, 1]\n        color_b: [0, 0, 0]\n        transformation:\n          - {transformation_type: matrix, matrix: [[0, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]]}\n");
+        assert_eq!(e.path, "objects[0].material.pattern.transformation");
+    }
+
+    #[test]
+    fn scene_hash_follows_the_scene_only() {
+        let scene = |yaml: &str| Config::from_yaml(yaml).unwrap().scene_hash();
+        let lit = scene("light: {position: [1, 2, 3]}\n");
+        assert_eq!(
+            lit,
+            scene("light: {position: [1, 2, 3]}\nrender: {tile_size: 8}\n")
+        );
+        assert_ne!(lit, scene("light: {position: [1, 2, 4]}\n"));
+    }
+
+    #[test]
+    fn previews_are_small_and_quick() {
+        let config = Config::from_yaml(
+            "camera: {width: 800, samples: 16}\nrender: {checkpoint: draft.ckpt, aovs: [depth]}\n",
+        )
+        .unwrap();
+        let preview = config.preview(4);
+        let camera = preview.camera.as_ref().unwrap();
+        assert_eq!((camera.width, camera.height), (Some(200), Some(300)));
+        assert_eq!(camera.samples, Some(1));
+        assert_eq!(preview.aovs().unwrap(), vec![]);
+        assert_eq!(preview.render.unwrap().checkpoint, None);
+        assert_eq!(
+            Config::default().preview(5000).camera.unwrap().width,
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn worlds_round_trip_through_yaml() {
+        let mut world = World::default();
+        let mut pattern = Pattern::stripe(WHITE, Color::new(0.0, 0.0, 1.0));
+        pattern.set_transformation(scaling(0.5, 0.5, 0.5));
+        world.objects[1].material.pattern = Some(pattern);
+        world.add_shape(
+            Object::builder()
+                .shape(Shape::Cylinder(-1.0, 2.0, true))
+                .transformation(translation(1.0, 2.0, 3.0) * rotation_y(0.5))
+                .build(),
+        );
+        world.fog = Some(Fog::new(WHITE, 0.1, 0.5));
+        world.background = Background::Gradient(WHITE, Color::new(0.2, 0.3, 0.8));
+        let yaml = Config::from_world(&world).unwrap().to_yaml();
+        assert!(!yaml.contains("null"));
+        let (loaded, _) = Config::from_yaml(&yaml).unwrap().build().unwrap();
+        assert_eq!(loaded.objects, world.objects);
+        assert_eq!(loaded.light, world.light);
+        assert_eq!(loaded.fog, world.fog);
+        assert_eq!(loaded.background, world.background);
+    }
+
+    #[test]
+    fn unwritable_objects_are_located() {
+        let mut world = World::default();
+        world.objects[1].material.pattern = Some(Pattern::test());
+        let e = Config::from_world(&world).unwrap_err();
+        assert_eq!(e.path, "objects[1].material.pattern.pattern_type");
+    }
+
+    #[test]
+    fn cameras_round_trip() {
+        let config = CameraConfig {
+            from: Some(TupleConfig(1.0, 2.0, -5.0)),
+            to: Some(TupleConfig(0.0, 1.0, 0.0)),
+            width: Some(40),
+            height: Some(30),
+            ..Default::default()
+        };
+        let camera = Camera::try_from(config).unwrap();
+        let loaded = Camera::try_from(CameraConfig::from(&camera)).unwrap();
+        assert_eq!((loaded.hsize, loaded.vsize), (40.0, 30.0));
+        assert!((loaded.focal_distance - camera.focal_distance).abs() < 1e-9);
+        for (a, b) in loaded.transform.data.iter().zip(&camera.transform.data) {
+            for (a, b) in a.iter().zip(b) {
+                assert!((a - b).abs() < 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::Value;
+
+use super::{
+    config::{Config, MaterialConfig, ObjectConfig, PatternConfig},
+    error::ConfigError,
+};
+
+// a configuration section that can start from a named entry and override
+// some of its fields
+pub(crate) trait Extend: Clone + Serialize + DeserializeOwned {
+    fn take_extend(&mut self) -> Option<String>;
+}
+
+impl Extend for PatternConfig {
+    fn take_extend(&mut self) -> Option<String> {
+        self.extend.take()
+    }
+}
+
+impl Extend for MaterialConfig {
+    fn take_extend(&mut self) -> Option<String> {
+        self.extend.take()
+    }
+}
+
+impl Extend for ObjectConfig {
+    fn take_extend(&mut self) -> Option<String> {
+        self.extend.take()
+    }
+}
+
+// the named patterns, materials and objects of a scene with every `extend`
+// already followed
+#[derive(Debug, Default)]
+pub(crate) struct Definitions {
+    patterns: BTreeMap<String, PatternConfig>,
+    materials: BTreeMap<String, MaterialConfig>,
+    objects: BTreeMap<String, ObjectConfig>,
+}
+
+impl Definitions {
+    pub(crate) fn new(config: &Config) -> Result<Self, ConfigError> {
+        let mut definitions = Definitions::default();
+        definitions.patterns =
+            resolve_section("patterns", config.patterns.as_ref(), Ok::<_, ConfigError>)?;
+        definitions.materials = resolve_section("materials", config.materials.as_ref(), |m| {
+            definitions.material_fields(m)
+        })?;
+        definitions.objects = resolve_section("define", config.define.as_ref(), |o| {
+            definitions.object_fields(o)
+        })?;
+        Ok(definitions)
+    }
+
+    pub(crate) fn pattern(&self, pattern: PatternConfig) -> Result<PatternConfig, ConfigError> {
+        lookup(&self.patterns, pattern)
+    }
+
+    pub(crate) fn material(&self, material: MaterialConfig) -> Result<MaterialConfig, ConfigError> {
+        self.material_fields(lookup(&self.materials, material)?)
+    }
+
+    pub(crate) fn object(&self, object: ObjectConfig) -> Result<ObjectConfig, ConfigError> {
+        self.object_fields(lookup(&self.objects, object)?)
+    }
+
+    fn material_fields(&self, mut material: MaterialConfig) -> Result<MaterialConfig, ConfigError> {
+        let pattern = |field: &str, value: Option<PatternConfig>| {
+            value
+                .map(|p| self.pattern(p).map_err(|e| e.within(field)))
+                .transpose()
+        };
+        material.pattern = pattern("pattern", material.pattern)?;
+        material.roughness_map = pattern("roughness_map", material.roughness_map)?;
+        material.metallic_map = pattern("metallic_map", material.metallic_map)?;
+        Ok(material)
+    }
+
+    fn object_fields(&self, mut object: ObjectConfig) -> Result<ObjectConfig, ConfigError> {
+        if let Some(material) = object.material {
+            object.material = Some(self.material(material).map_err(|e| e.within("material"))?);
+        }
+        Ok(object)
+    }
+}
+
+// follows the `extend` chains within one section, so entries may build on
+// each other in any order
+fn resolve_section<T: Extend>(
+    section: &str,
+    raw: Option<&BTreeMap<String, T>>,
+    fields: impl Fn(T) -> Result<T, ConfigError>,
+) -> Result<BTreeMap<String, T>, ConfigError> {
+    let empty = BTreeMap::new();
+    let raw = raw.unwrap_or(&empty);
+    let mut resolved = BTreeMap::new();
+    for name in raw.keys() {
+        resolve_entry(name, raw, &mut resolved, &mut vec![], &fields)
+            .map_err(|e| e.within(section))?;
+    }
+    Ok(resolved)
+}
+
+fn resolve_entry<T: Extend>(
+    name: &str,
+    raw: &BTreeMap<String, T>,
+    resolved: &mut BTreeMap<String, T>,
+    chain: &mut Vec<String>,
+    fields: &impl Fn(T) -> Result<T, ConfigError>,
+) -> Result<(), ConfigError> {
+    if resolved.contains_key(name) {
+        return Ok(());
+    }
+    if let Some(start) = chain.iter().position(|n| n == name) {
+        // reported at the entry that closes the loop
+        let closing = chain.last().unwrap().clone();
+        chain.push(name.to_string());
+        return Err(ConfigError::new(
+            "extend",
+            &format!("circular definition: {}", chain[start..].join(" -> ")),
+        )
+        .within(&closing));
+    }
+    chain.push(name.to_string());
+    let mut value = raw[name].clone();
+    if let Some(base) = value.take_extend() {
+        if !raw.contains_key(&base) {
+            return Err(unknown_name(&base, raw).within(name));
+        }
+        resolve_entry(&base, raw, resolved, chain, fields)?;
+        value = merge(value, &resolved[&base]);
+    }
+    let value = fields(value).map_err(|e| e.within(name))?;
+    chain.pop();
+    resolved.insert(name.to_string(), value);
+    Ok(())
+}
+
+fn lookup<T: Extend>(definitions: &BTreeMap<String, T>, mut value: T) -> Result<T, ConfigError> {
+    match value.take_extend() {
+        None => Ok(value),
+        Some(name) => match definitions.get(&name) {
+            Some(base) => Ok(merge(value, base)),
+            None => Err(unknown_name(&name, definitions)),
+        },
+    }
+}
+
+fn unknown_name<T>(name: &str, definitions: &BTreeMap<String, T>) -> ConfigError {
+    let names: Vec<_> = definitions.keys().map(String::as_str).collect();
+    match names.is_empty() {
+        true => ConfigError::new("extend", &format!("nothing named `{}` is defined", name)),
+        false => ConfigError::unknown("extend", name, &names),
+    }
+}
+
+// fields set on `value` win; nested sections are merged field by field while
+// lists, such as transformations, are replaced as a whole
+fn merge<T: Extend>(value: T, base: &T) -> T {
+    let merged = merge_values(
+        serde_yaml::to_value(base).unwrap(),
+        serde_yaml::to_value(value).unwrap(),
+    );
+    serde_yaml::from_value(merged).unwrap()
+}
+
+fn merge_values(base: Value, over: Value) -> Value {
+    match (base, over) {
+        (Value::Mapping(mut base), Value::Mapping(over)) => {
+            for (key, value) in over {
+                let merged = match base.remove(&key) {
+                    Some(old) => merge_values(old, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Mapping(base)
+        }
+        (base, Value::Null) => base,
+        (_, over) => over,
+    }
+}
+
+#[cfg(test)]
+mod definitions_tests {
+    use crate::scene::TupleConfig;
+
+    use super::*;
+
+    fn resolve(yaml: &str) -> Result<Config, ConfigError> {
+        Config::from_yaml(yaml).unwrap().resolve()
+    }
+
+    const SCENE: &str = "
+patterns:
+  tiles: {pattern_type: checker, color_a: [1, 1, 1], color_b: [0.5, 0.5, 0.5]}
+materials:
+  tiled: {pattern: {extend: tiles}, specular: 0}
+  shiny: {extend: tiled, reflective: 0.5}
+define:
+  wall:
+    shape: {shape_type: plane}
+    material: {extend: shiny}
+    transformation: [{transformation_type: rotation_x, rad: 1.5}]
+objects:
+  - extend: wall
+    material: {color: [1, 0, 0], pattern: {color_b: [0, 0, 1]}}
+  - material: {extend: tiled}
+";
+
+    #[test]
+    fn names_are_replaced_by_their_fields() {
+        let config = resolve(SCENE).unwrap();
+        assert_eq!(config.define, None);
+        let objects = config.objects.unwrap();
+        let wall = &objects[0];
+        assert_eq!(wall.extend, None);
+        assert_eq!(wall.transformation.as_ref().unwrap().len(), 1);
+        let material = wall.material.as_ref().unwrap();
+        assert_eq!(material.reflective, Some(0.5));
+        assert_eq!(material.specular, Some(0.0));
+        assert_eq!(material.color, Some(TupleConfig(1.0, 0.0, 0.0)));
+        let pattern = material.pattern.as_ref().unwrap();
+        assert_eq!(pattern.pattern_type.as_deref(), Some("checker"));
+        assert_eq!(pattern.color_a, Some(TupleConfig(1.0, 1.0, 1.0)));
+        assert_eq!(pattern.color_b, Some(TupleConfig(0.0, 0.0, 1.0)));
+        let tiled = objects[1].material.as_ref().unwrap();
+        assert_eq!(tiled.reflective, None);
+        assert!(tiled.pattern.is_some());
+    }
+
+    #[test]
+    fn unknown_names_are_located() {
+        let e = resolve("materials: {shiny: {}}\nobjects:\n  - material: {extend: shiy}\n")
+            .unwrap_err();
+        assert_eq!(e.path, "objects[0].material.extend");
+        assert_eq!(e.suggestion.as_deref(), Some("did you mean `shiny`?"));
+        let e = resolve("materials: {shiny: {pattern: {extend: tiles}}}\n").unwrap_err();
+        assert_eq!(e.path, "materials.shiny.pattern.extend");
+    }
+
+    #[test]
+    fn circular_definitions_are_rejected() {
+        let e =
+            resolve("define:\n  a: {extend: b}\n  b: {extend: c}\n  c: {extend: b}\n").unwrap_err();
+        assert_eq!(e.path, "define.c.extend");
+        assert_eq!(e.message, "circular definition: b -> c -> b");
+    }
+}
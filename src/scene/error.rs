@@ -0,0 +1,116 @@
+use std::{error::Error, fmt};
+
+// a problem in the scene description, located by its path in the YAML
+// document such as `objects[3].shape.min`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub path: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl ConfigError {
+    pub fn new(field: &str, message: &str) -> Self {
+        ConfigError {
+            path: field.to_string(),
+            message: message.to_string(),
+            suggestion: None,
+        }
+    }
+
+    pub fn missing(field: &str) -> Self {
+        ConfigError::new(field, "missing value")
+    }
+
+    pub fn unknown(field: &str, value: &str, expected: &[&str]) -> Self {
+        let mut error = ConfigError::new(
+            field,
+            &format!(
+                "unknown value `{}`, expected one of: {}",
+                value,
+                expected.join(", ")
+            ),
+        );
+        error.suggestion = closest(value, expected).map(|s| format!("did you mean `{}`?", s));
+        error
+    }
+
+    // prefixes the path with the field or index that contains it
+    pub fn within(mut self, parent: &str) -> Self {
+        self.path = match self.path.as_str() {
+            "" => parent.to_string(),
+            path if path.starts_with('[') => format!("{}{}", parent, path),
+            path => format!("{}.{}", parent, path),
+        };
+        self
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // errors about the document as a whole, such as bad syntax, have no path
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({})", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ConfigError {}
+
+pub(crate) fn required<T>(value: Option<T>, field: &str) -> Result<T, ConfigError> {
+    value.ok_or_else(|| ConfigError::missing(field))
+}
+
+// the expected value closest to a misspelt one, if any is close enough
+fn closest<'a>(value: &str, expected: &[&'a str]) -> Option<&'a str> {
+    expected
+        .iter()
+        .map(|candidate| (edit_distance(value, candidate), *candidate))
+        .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("cone", "cone"), 0);
+        assert_eq!(edit_distance("cylnder", "cylinder"), 1);
+        assert_eq!(edit_distance("stripe", "strip"), 1);
+        assert_eq!(edit_distance("ring", "king"), 1);
+        assert_eq!(edit_distance("", "cube"), 4);
+    }
+
+    #[test]
+    fn paths_are_joined_from_the_inside_out() {
+        let e = ConfigError::missing("min")
+            .within("shape")
+            .within("[2]")
+            .within("objects");
+        assert_eq!(e.to_string(), "objects[2].shape.min: missing value");
+        assert_eq!(ConfigError::new("", "bad syntax").to_string(), "bad syntax");
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+use super::error::ConfigError;
+
+// the serialisations a scene can be written in; JSON and TOML are handy for
+// scenes produced by other programs
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Format {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Yaml, Format::Json, Format::Toml];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Yaml => "yaml",
+            Format::Json => "json",
+            Format::Toml => "toml",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "yml" => Some(Format::Yaml),
+            _ => Format::ALL.into_iter().find(|f| f.name() == name),
+        }
+    }
+
+    // chosen by extension, anything unknown being read as YAML
+    pub fn from_path(path: &Path) -> Format {
+        path.extension()
+            .and_then(|e| Format::from_name(&e.to_string_lossy().to_lowercase()))
+            .unwrap_or_default()
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self, text: &str) -> Result<T, ConfigError> {
+        let result = match self {
+            Format::Yaml => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+            Format::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+        };
+        result.map_err(|e| ConfigError::new("", &e))
+    }
+}
+
+#[cfg(test)]
+mod format_tests {
+    use crate::scene::{Config, TupleConfig};
+
+    use super::*;
+
+    #[test]
+    fn formats_follow_the_extension() {
+        assert_eq!(Format::from_path(Path::new("a/scene.json")), Format::Json);
+        assert_eq!(Format::from_path(Path::new("scene.TOML")), Format::Toml);
+        assert_eq!(Format::from_path(Path::new("scene.yml")), Format::Yaml);
+        assert_eq!(Format::from_path(Path::new("scene")), Format::Yaml);
+    }
+
+    #[test]
+    fn every_format_reads_the_same_scene() {
+        let yaml = "light: {position: [1, 2, 3]}\nobjects:\n  - shape: {shape_type: cube}\n";
+        let json =
+            r#"{"light": {"position": [1, 2, 3]}, "objects": [{"shape": {"shape_type": "cube"}}]}"#;
+        let toml = "light.position = [1, 2, 3]\n[[objects]]\nshape.shape_type = \"cube\"\n";
+        let scenes: Vec<Config> = [
+            (Format::Yaml, yaml),
+            (Format::Json, json),
+            (Format::Toml, toml),
+        ]
+        .iter()
+        .map(|(format, text)| format.parse(text).unwrap())
+        .collect();
+        let light = scenes[0].light.as_ref().unwrap();
+        assert_eq!(light.position, Some(TupleConfig(1.0, 2.0, 3.0)));
+        assert_eq!(scenes[1], scenes[0]);
+        assert_eq!(scenes[2], scenes[0]);
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        assert!(Format::Json.parse::<Config>("{\"objects\": [}").is_err());
+        assert!(Format::Toml.parse::<Config>("objects = [").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    config::{Config, ObjectConfig},
+    error::ConfigError,
+    format::Format,
+};
+
+// the files behind one scene: those being read, innermost last, to catch
+// circular includes, and every file named so far, readable or not
+#[derive(Debug, Default)]
+pub(crate) struct Files {
+    stack: Vec<PathBuf>,
+    pub(crate) read: Vec<PathBuf>,
+}
+
+// reads a scene and every file it includes or instances, with relative
+// paths taken from the directory of the file that names them
+pub(crate) fn load(path: &Path, files: &mut Files) -> Result<Config, ConfigError> {
+    if !files.read.iter().any(|p| p == path) {
+        files.read.push(path.to_path_buf());
+    }
+    let unreadable = |e: std::io::Error| {
+        ConfigError::new("", &format!("unable to read `{}`: {}", path.display(), e))
+    };
+    let canonical = path.canonicalize().map_err(unreadable)?;
+    let stack = &mut files.stack;
+    if let Some(start) = stack.iter().position(|p| *p == canonical) {
+        let cycle: Vec<_> = stack[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(ConfigError::new(
+            "",
+            &format!("circular include: {}", cycle.join(" -> ")),
+        ));
+    }
+    let text = fs::read_to_string(path).map_err(unreadable)?;
+    stack.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let result = expand(&text, Format::from_path(path), dir, files);
+    files.stack.pop();
+    result
+}
+
+// a scene given as text, whose includes are relative to `dir`
+pub(crate) fn expand(
+    text: &str,
+    format: Format,
+    dir: &Path,
+    files: &mut Files,
+) -> Result<Config, ConfigError> {
+    let mut config: Config = format.parse(text)?;
+    rebase(&mut config, dir);
+    let mut base = Config::default();
+    let includes = config.include.take().unwrap_or_default();
+    for (i, include) in includes.iter().enumerate() {
+        let included = load(&dir.join(include), files)
+            .map_err(|e| e.within(&format!("[{}]", i)).within("include"))?;
+        base = layer(included, base);
+    }
+    if let Some(objects) = config.objects.take() {
+        let mut expanded = vec![];
+        for (i, object) in objects.into_iter().enumerate() {
+            match object.instance.clone() {
+                Some(file) => expanded.extend(
+                    instance(object, &dir.join(file), files)
+                        .map_err(|e| e.within(&format!("[{}]", i)).within("objects"))?,
+                ),
+                None => expanded.push(object),
+            }
+        }
+        config.objects = Some(expanded);
+    }
+    Ok(layer(config, base))
+}
+
+// the files a scene reads or writes are named relative to it, like its
+// includes, so they still resolve once it is included from elsewhere
+fn rebase(config: &mut Config, dir: &Path) {
+    let paths = [
+        config.background.as_mut().and_then(|b| b.path.as_mut()),
+        config.render.as_mut().and_then(|r| r.checkpoint.as_mut()),
+    ];
+    for path in paths.into_iter().flatten() {
+        *path = dir.join(&*path).to_string_lossy().into_owned();
+    }
+}
+
+// the objects of another scene file placed under the entry's transformation,
+// which applies on top of each object's own
+fn instance(
+    entry: ObjectConfig,
+    path: &Path,
+    files: &mut Files,
+) -> Result<Vec<ObjectConfig>, ConfigError> {
+    if entry.extend.is_some()
+        || entry.shape.is_some()
+        || entry.material.is_some()
+        || entry.motion.is_some()
+    {
+        return Err(ConfigError::new(
+            "instance",
+            "an instance only takes a transformation",
+        ));
+    }
+    let group = load(path, files)
+        .and_then(Config::resolve)
+        .map_err(|e| e.within("instance"))?;
+    let placement = entry.transformation.unwrap_or_default();
+    let place = |list: Vec<_>| placement.iter().cloned().chain(list).collect();
+    Ok(group
+        .objects
+        .unwrap_or_default()
+        .into_iter()
+        .map(|object| ObjectConfig {
+            transformation: Some(place(object.transformation.unwrap_or_default())),
+            motion: object.motion.map(place),
+            ..object
+        })
+        .collect())
+}
+
+// `config` on top of `base`: its settings win, named entries override those
+// of the same name and its objects come after the base's
+fn layer(config: Config, base: Config) -> Config {
+    Config {
+        include: None,
+        light: config.light.or(base.light),
+        camera: config.camera.or(base.camera),
+        patterns: joined(base.patterns, config.patterns),
+        materials: joined(base.materials, config.materials),
+        define: joined(base.define, config.define),
+        objects: joined(base.objects, config.objects),
+        fog: config.fog.or(base.fog),
+        background: config.background.or(base.background),
+        image_based_lighting: config.image_based_lighting.or(base.image_based_lighting),
+        output_file: config.output_file.or(base.output_file),
+        animation: config.animation.or(base.animation),
+        render: config.render.or(base.render),
+    }
+}
+
+// a list or map with the entries of `over` added after, or in place of,
+// those of `base`
+fn joined<C: IntoIterator + Extend<C::Item>>(base: Option<C>, over: Option<C>) -> Option<C> {
+    match (base, over) {
+        (Some(mut base), Some(over)) => {
+            base.extend(over);
+            Some(base)
+        }
+        (base, over) => over.or(base),
+    }
+}
+
+#[cfg(test)]
+mod include_tests {
+    use super::*;
+
+    // writes the files of a scene into a fresh directory
+    fn scene(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        for (file, text) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn includes_are_layered_underneath() {
+        let dir = scene(
+            "rt_includes_are_layered_underneath",
+            &[
+                (
+                    "main.yaml",
+                    "include: [rigs/light.yaml]\nfog: {density: 0.2}\nobjects:\n  - material: {extend: red}\n",
+                ),
+                (
+                    "rigs/light.yaml",
+                    "include: [../props.yaml]\nlight: {position: [1, 2, 3]}\nfog: {density: 0.1}\n",
+                ),
+                (
+                    "props.yaml",
+                    "materials: {red: {color: [1, 0, 0]}}\nobjects:\n  - shape: {shape_type: cube}\n",
+                ),
+            ],
+        );
+        let config = Config::load(dir.join("main.yaml")).unwrap();
+        assert_eq!(config.include, None);
+        assert_eq!(config.fog.as_ref().unwrap().density, Some(0.2));
+        assert!(config.light.is_some());
+        let objects = config.objects.clone().unwrap();
+        assert_eq!(objects.len(), 2);
+        assert!(objects[0].shape.is_some());
+        let (world, _) = config.build().unwrap();
+        assert_eq!(world.objects[1].material.color.rgb.y, 0.0);
+    }
+
+    #[test]
+    fn instances_place_a_file_under_each_transformation() {
+        let dir = scene(
+            "rt_instances_place_a_file",
+            &[
+                (
+                    "main.yaml",
+                    "objects:\n  - instance: chair.yaml\n  - instance: chair.yaml\n    transformation: [{transformation_type: translation, vec: [5, 0, 0]}]\n",
+                ),
+                (
+                    "chair.yaml",
+                    "define: {leg: {shape: {shape_type: cube}}}\nobjects:\n  - extend: leg\n    transformation: [{transformation_type: scaling, vec: [1, 2, 1]}]\n  - extend: leg\n",
+                ),
+            ],
+        );
+        let (world, _) = Config::load(dir.join("main.yaml"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(world.objects.len(), 4);
+        let moved = world.objects[2].transformation();
+        assert_eq!((moved.data[0][3], moved.data[1][1]), (5.0, 2.0));
+        assert_eq!(world.objects[3].transformation().data[0][3], 5.0);
+    }
+
+    #[test]
+    fn included_files_may_use_other_formats() {
+        let dir = scene(
+            "rt_included_files_may_use_other_formats",
+            &[
+                ("main.toml", "include = [\"light.json\"]\n"),
+                ("light.json", r#"{"light": {"position": [1, 2, 3]}}"#),
+            ],
+        );
+        let config = Config::load(dir.join("main.toml")).unwrap();
+        assert!(config.light.is_some());
+    }
+
+    #[test]
+    fn every_file_read_is_recorded() {
+        let dir = scene(
+            "rt_every_file_read_is_recorded",
+            &[
+                ("main.yaml", "include: [light.yaml, lamp.yaml]\n"),
+                ("light.yaml", "objects:\n  - instance: lamp.yaml\n"),
+                ("lamp.yaml", "objects:\n  - instance: shade.yaml\n"),
+            ],
+        );
+        let mut files = vec![];
+        assert!(Config::load_recording(dir.join("main.yaml"), &mut files).is_err());
+        let names: Vec<_> = files.iter().map(|p| p.file_name().unwrap()).collect();
+        assert_eq!(
+            names,
+            ["main.yaml", "light.yaml", "lamp.yaml", "shade.yaml"]
+        );
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_file_naming_them() {
+        let dir = scene(
+            "rt_paths_are_relative",
+            &[
+                ("main.yaml", "include: [rigs/rig.yaml]\n"),
+                (
+                    "rigs/rig.yaml",
+                    "background: {background_type: environment, path: env.png}\nrender: {checkpoint: rig.ckpt}\n",
+                ),
+            ],
+        );
+        let config = Config::load(dir.join("main.yaml")).unwrap();
+        let path = config.background.unwrap().path.unwrap();
+        assert_eq!(PathBuf::from(path), dir.join("rigs/env.png"));
+        let checkpoint = config.render.unwrap().checkpoint.unwrap();
+        assert_eq!(PathBuf::from(checkpoint), dir.join("rigs/rig.ckpt"));
+    }
+
+    #[test]
+    fn circular_includes_are_rejected() {
+        let dir = scene(
+            "rt_circular_includes_are_rejected",
+            &[
+                ("a.yaml", "include: [b.yaml]\n"),
+                ("b.yaml", "objects:\n  - instance: a.yaml\n"),
+            ],
+        );
+        let e = Config::load(dir.join("a.yaml")).unwrap_err();
+        assert_eq!(e.path, "include[0].objects[0].instance");
+        assert!(e.message.starts_with("circular include:"));
+    }
+
+    #[test]
+    fn includes_need_a_file() {
+        let e = Config::from_yaml("include: [lights.yaml]\n")
+            .unwrap()
+            .resolve()
+            .unwrap_err();
+        assert_eq!(e.path, "include");
+    }
+}
@@ -0,0 +1,161 @@
+use jsonschema::{paths::PathChunk, JSONSchema};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{ArrayValidation, InstanceType, RootSchema, Schema, SchemaObject},
+    visit::{visit_schema_object, Visitor},
+};
+use serde_json::Value;
+
+use crate::features::Aov;
+
+use super::{animation::EASINGS, config::*, error::ConfigError};
+
+// JSON Schema of the scene format, for editors and `validate`
+pub fn schema() -> RootSchema {
+    // unset fields are simply left out, which keeps the schema flat enough
+    // for validation errors to name the offending field
+    SchemaSettings::draft07()
+        .with(|s| s.option_add_null_type = false)
+        .with_visitor(NoNullDefaults)
+        .into_generator()
+        .into_root_schema_for::<Config>()
+}
+
+// `Option` fields described by hand are given a default of None to keep
+// them optional, which would otherwise show up as `"default": null`
+#[derive(Debug, Clone)]
+struct NoNullDefaults;
+
+impl Visitor for NoNullDefaults {
+    fn visit_schema_object(&mut self, schema: &mut SchemaObject) {
+        if let Some(metadata) = &mut schema.metadata {
+            if metadata.default == Some(Value::Null) {
+                metadata.default = None;
+            }
+        }
+        visit_schema_object(self, schema);
+    }
+}
+
+// checks a scene document, as parsed from YAML or JSON, against the schema
+pub fn validate(document: &Value) -> Result<(), Vec<ConfigError>> {
+    let schema = serde_json::to_value(schema()).unwrap();
+    let compiled = JSONSchema::compile(&schema).expect("Invalid scene schema");
+    compiled.validate(document).map_err(|errors| {
+        errors
+            .map(|e| ConfigError::new(&location(e.instance_path.clone()), &e.to_string()))
+            .collect()
+    })
+}
+
+// `/objects/0/shape` as `objects[0].shape`
+fn location(pointer: impl IntoIterator<Item = PathChunk>) -> String {
+    let mut path = String::new();
+    for chunk in pointer {
+        match chunk {
+            PathChunk::Index(i) => path += &format!("[{}]", i),
+            PathChunk::Property(name) if path.is_empty() => path += &name,
+            PathChunk::Property(name) => path += &format!(".{}", name),
+            PathChunk::Keyword(_) => {}
+        }
+    }
+    path
+}
+
+fn one_of(values: &[&str]) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(values.iter().map(|v| Value::from(*v)).collect()),
+        ..Default::default()
+    }
+    .into()
+}
+
+pub(crate) fn tile_orders(_: &mut SchemaGenerator) -> Schema {
+    one_of(TILE_ORDERS)
+}
+
+pub(crate) fn stats(_: &mut SchemaGenerator) -> Schema {
+    one_of(STATS)
+}
+
+pub(crate) fn aovs(_: &mut SchemaGenerator) -> Schema {
+    let names: Vec<_> = Aov::ALL.iter().map(Aov::name).collect();
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(one_of(&names).into()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+pub(crate) fn background_types(_: &mut SchemaGenerator) -> Schema {
+    one_of(BACKGROUND_TYPES)
+}
+
+pub(crate) fn projections(_: &mut SchemaGenerator) -> Schema {
+    one_of(PROJECTIONS)
+}
+
+pub(crate) fn filter_types(_: &mut SchemaGenerator) -> Schema {
+    one_of(FILTER_TYPES)
+}
+
+pub(crate) fn shadings(_: &mut SchemaGenerator) -> Schema {
+    one_of(SHADINGS)
+}
+
+pub(crate) fn pattern_types(_: &mut SchemaGenerator) -> Schema {
+    one_of(PATTERN_TYPES)
+}
+
+pub(crate) fn shape_types(_: &mut SchemaGenerator) -> Schema {
+    one_of(SHAPE_TYPES)
+}
+
+pub(crate) fn transformation_types(_: &mut SchemaGenerator) -> Schema {
+    one_of(TRANSFORMATION_TYPES)
+}
+
+pub(crate) fn easings(_: &mut SchemaGenerator) -> Schema {
+    one_of(EASINGS)
+}
+
+#[cfg(test)]
+mod schema_tests {
+    use super::*;
+
+    fn errors(yaml: &str) -> Vec<ConfigError> {
+        validate(&serde_yaml::from_str(yaml).unwrap()).unwrap_err()
+    }
+
+    #[test]
+    fn committed_schema_is_up_to_date() {
+        let committed: Value = serde_json::from_str(include_str!("../../rt-config.json")).unwrap();
+        assert!(
+            committed == serde_json::to_value(schema()).unwrap(),
+            "rt-config.json is stale, regenerate it with `cargo run -- schema -o rt-config.json`"
+        );
+    }
+
+    #[test]
+    fn example_scene_is_valid() {
+        let scene = serde_yaml::from_str(include_str!("../../config.yaml")).unwrap();
+        assert_eq!(validate(&scene), Ok(()));
+    }
+
+    #[test]
+    fn errors_name_the_offending_field() {
+        let e = errors("objects:\n  - shape: {shape_type: torus}\n");
+        assert_eq!(e.len(), 1);
+        assert_eq!(e[0].path, "objects[0].shape.shape_type");
+        let e = errors("camera: {width: 0, from: [1, 2]}\n");
+        let paths: Vec<_> = e.iter().map(|e| e.path.as_str()).collect();
+        assert!(paths.contains(&"camera.width"));
+        assert!(paths.contains(&"camera.from"));
+        assert_eq!(errors("lights: []\n").len(), 1);
+    }
+}